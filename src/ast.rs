@@ -1,36 +1,82 @@
-use crate::token::{Token, TokenType};
+use crate::token::{Span, Token, TokenType};
 
-#[derive(Debug)]
+/// Handle to an expression node stored in an `Ast` arena.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ExprId(usize);
+
+impl ExprId {
+    /// Position of the node in its arena. Unique among the expressions of
+    /// one `Ast`.
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// Handle to a statement node stored in an `Ast` arena.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StmtId(usize);
+
+impl StmtId {
+    /// Position of the node in its arena. Unique among the statements of
+    /// one `Ast`.
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// Handle to either kind of node, used where both can appear, such as the
+/// parent of an expression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NodeId {
+    Expr(ExprId),
+    Stmt(StmtId),
+}
+
+#[derive(Debug, Clone)]
 pub struct BinaryNode {
-    left: Box<ASTNode>,
+    left: ExprId,
     operator: Token,
-    right: Box<ASTNode>,
+    right: ExprId,
 }
 
 impl BinaryNode {
-    pub fn new(left: ASTNode, operator: Token, right: ASTNode) -> Self {
+    pub fn new(left: ExprId, operator: Token, right: ExprId) -> Self {
         BinaryNode {
-            left: Box::new(left),
+            left,
             operator,
-            right: Box::new(right),
+            right,
         }
     }
+
+    pub fn left(&self) -> ExprId {
+        self.left
+    }
+
+    pub fn operator(&self) -> &Token {
+        &self.operator
+    }
+
+    pub fn right(&self) -> ExprId {
+        self.right
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct GroupingNode {
-    child: Box<ASTNode>,
+    child: ExprId,
 }
 
 impl GroupingNode {
-    pub fn new(child: ASTNode) -> Self {
-        GroupingNode {
-            child: Box::new(child),
-        }
+    pub fn new(child: ExprId) -> Self {
+        GroupingNode { child }
+    }
+
+    pub fn child(&self) -> ExprId {
+        self.child
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct LiteralNode {
     value: TokenType,
 }
@@ -39,91 +85,541 @@ impl LiteralNode {
     pub fn new(value: TokenType) -> Self {
         LiteralNode { value }
     }
+
+    pub fn value(&self) -> &TokenType {
+        &self.value
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct UnaryNode {
     operator: Token,
-    child: Box<ASTNode>,
+    child: ExprId,
 }
 
 impl UnaryNode {
-    pub fn new(operator: Token, child: ASTNode) -> Self {
-        UnaryNode {
+    pub fn new(operator: Token, child: ExprId) -> Self {
+        UnaryNode { operator, child }
+    }
+
+    pub fn operator(&self) -> &Token {
+        &self.operator
+    }
+
+    pub fn child(&self) -> ExprId {
+        self.child
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VariableNode {
+    name: Token,
+}
+
+impl VariableNode {
+    pub fn new(name: Token) -> Self {
+        VariableNode { name }
+    }
+
+    pub fn name(&self) -> &Token {
+        &self.name
+    }
+}
+
+/// `name = value`, or a compound assignment such as `name += value` when
+/// `operator` is not `=`.
+#[derive(Debug, Clone)]
+pub struct AssignNode {
+    name: Token,
+    operator: Token,
+    value: ExprId,
+}
+
+impl AssignNode {
+    pub fn new(name: Token, operator: Token, value: ExprId) -> Self {
+        AssignNode {
+            name,
+            operator,
+            value,
+        }
+    }
+
+    pub fn name(&self) -> &Token {
+        &self.name
+    }
+
+    pub fn operator(&self) -> &Token {
+        &self.operator
+    }
+
+    pub fn value(&self) -> ExprId {
+        self.value
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CallNode {
+    callee: ExprId,
+    /// The closing parenthesis, whose line is reported for errors raised
+    /// by the call.
+    paren: Token,
+    arguments: Vec<ExprId>,
+}
+
+impl CallNode {
+    pub fn new(callee: ExprId, paren: Token, arguments: Vec<ExprId>) -> Self {
+        CallNode {
+            callee,
+            paren,
+            arguments,
+        }
+    }
+
+    pub fn callee(&self) -> ExprId {
+        self.callee
+    }
+
+    pub fn paren(&self) -> &Token {
+        &self.paren
+    }
+
+    pub fn arguments(&self) -> &[ExprId] {
+        &self.arguments
+    }
+}
+
+/// `condition ? then_branch : else_branch`. Only the chosen branch is
+/// evaluated.
+#[derive(Debug, Clone)]
+pub struct ConditionalNode {
+    condition: ExprId,
+    then_branch: ExprId,
+    else_branch: ExprId,
+}
+
+impl ConditionalNode {
+    pub fn new(condition: ExprId, then_branch: ExprId, else_branch: ExprId) -> Self {
+        ConditionalNode {
+            condition,
+            then_branch,
+            else_branch,
+        }
+    }
+
+    pub fn condition(&self) -> ExprId {
+        self.condition
+    }
+
+    pub fn then_branch(&self) -> ExprId {
+        self.then_branch
+    }
+
+    pub fn else_branch(&self) -> ExprId {
+        self.else_branch
+    }
+}
+
+/// `++name`, `--name`, `name++` or `name--`. The prefix forms give the
+/// updated value and the postfix forms the value from before.
+#[derive(Debug, Clone)]
+pub struct IncrementNode {
+    operator: Token,
+    name: Token,
+    prefix: bool,
+}
+
+impl IncrementNode {
+    pub fn new(operator: Token, name: Token, prefix: bool) -> Self {
+        IncrementNode {
             operator,
-            child: Box::new(child),
+            name,
+            prefix,
         }
     }
+
+    pub fn operator(&self) -> &Token {
+        &self.operator
+    }
+
+    pub fn name(&self) -> &Token {
+        &self.name
+    }
+
+    pub fn is_prefix(&self) -> bool {
+        self.prefix
+    }
+}
+
+/// A string with embedded expressions. `parts` alternate between string
+/// literals and expressions, though empty literals are left out.
+#[derive(Debug, Clone)]
+pub struct InterpolationNode {
+    parts: Vec<ExprId>,
+}
+
+impl InterpolationNode {
+    pub fn new(parts: Vec<ExprId>) -> Self {
+        InterpolationNode { parts }
+    }
+
+    pub fn parts(&self) -> &[ExprId] {
+        &self.parts
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ASTNode {
+    Assign(AssignNode),
     Binary(BinaryNode),
+    Call(CallNode),
+    Conditional(ConditionalNode),
     Grouping(GroupingNode),
+    Increment(IncrementNode),
+    Interpolation(InterpolationNode),
     Literal(LiteralNode),
     Unary(UnaryNode),
+    Variable(VariableNode),
     Error,
 }
 
 impl ASTNode {
-    pub fn pretty_print(&self) -> String {
-        let mut pretty = String::new();
-        pretty.push_str("digraph G {\n");
-        pretty_print_recursive(self, &mut pretty, 0);
-        pretty.push_str("}");
-        pretty
+    /// Returns the handles of this node's direct children, left to right.
+    pub fn children(&self) -> Vec<ExprId> {
+        match self {
+            ASTNode::Assign(node) => vec![node.value],
+            ASTNode::Binary(node) => vec![node.left, node.right],
+            ASTNode::Call(node) => {
+                let mut children = vec![node.callee];
+                children.extend(&node.arguments);
+                children
+            }
+            ASTNode::Conditional(node) => {
+                vec![node.condition, node.then_branch, node.else_branch]
+            }
+            ASTNode::Grouping(node) => vec![node.child],
+            ASTNode::Interpolation(node) => node.parts.clone(),
+            ASTNode::Unary(node) => vec![node.child],
+            ASTNode::Increment(_) | ASTNode::Literal(_) | ASTNode::Variable(_) | ASTNode::Error => {
+                vec![]
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ExpressionNode {
+    expression: ExprId,
+}
+
+impl ExpressionNode {
+    pub fn new(expression: ExprId) -> Self {
+        ExpressionNode { expression }
+    }
+
+    pub fn expression(&self) -> ExprId {
+        self.expression
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PrintNode {
+    expression: ExprId,
+}
+
+impl PrintNode {
+    pub fn new(expression: ExprId) -> Self {
+        PrintNode { expression }
+    }
+
+    pub fn expression(&self) -> ExprId {
+        self.expression
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VarNode {
+    name: Token,
+    initializer: Option<ExprId>,
+}
+
+impl VarNode {
+    pub fn new(name: Token, initializer: Option<ExprId>) -> Self {
+        VarNode { name, initializer }
+    }
+
+    pub fn name(&self) -> &Token {
+        &self.name
+    }
+
+    pub fn initializer(&self) -> Option<ExprId> {
+        self.initializer
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BlockNode {
+    statements: Vec<StmtId>,
+}
+
+impl BlockNode {
+    pub fn new(statements: Vec<StmtId>) -> Self {
+        BlockNode { statements }
+    }
+
+    pub fn statements(&self) -> &[StmtId] {
+        &self.statements
+    }
+}
+
+/// `if (condition) then_branch else else_branch`, where the `else` part is
+/// optional.
+#[derive(Debug, Clone)]
+pub struct IfNode {
+    condition: ExprId,
+    then_branch: StmtId,
+    else_branch: Option<StmtId>,
+}
+
+impl IfNode {
+    pub fn new(condition: ExprId, then_branch: StmtId, else_branch: Option<StmtId>) -> Self {
+        IfNode {
+            condition,
+            then_branch,
+            else_branch,
+        }
+    }
+
+    pub fn condition(&self) -> ExprId {
+        self.condition
+    }
+
+    pub fn then_branch(&self) -> StmtId {
+        self.then_branch
+    }
+
+    pub fn else_branch(&self) -> Option<StmtId> {
+        self.else_branch
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WhileNode {
+    condition: ExprId,
+    body: StmtId,
+}
+
+impl WhileNode {
+    pub fn new(condition: ExprId, body: StmtId) -> Self {
+        WhileNode { condition, body }
+    }
+
+    pub fn condition(&self) -> ExprId {
+        self.condition
+    }
+
+    pub fn body(&self) -> StmtId {
+        self.body
+    }
+}
+
+/// A function declaration. `body` is always a block, whose statements run
+/// in the same scope as the parameters.
+#[derive(Debug, Clone)]
+pub struct FunctionNode {
+    name: Token,
+    params: Vec<Token>,
+    body: StmtId,
+}
+
+impl FunctionNode {
+    pub fn new(name: Token, params: Vec<Token>, body: StmtId) -> Self {
+        FunctionNode { name, params, body }
+    }
+
+    pub fn name(&self) -> &Token {
+        &self.name
+    }
+
+    pub fn params(&self) -> &[Token] {
+        &self.params
+    }
+
+    pub fn body(&self) -> StmtId {
+        self.body
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ReturnNode {
+    value: Option<ExprId>,
+}
+
+impl ReturnNode {
+    pub fn new(value: Option<ExprId>) -> Self {
+        ReturnNode { value }
+    }
+
+    pub fn value(&self) -> Option<ExprId> {
+        self.value
     }
 }
 
-fn make_graphviz_label(node: &ASTNode, depth: u32) -> String {
-    match node {
-        ASTNode::Binary(node) => format!("{}_{}", node.operator.token_type, depth),
-        ASTNode::Grouping(_) => format!("group_{}", depth),
-        ASTNode::Literal(node) => format!("{}_{}", node.value, depth),
-        ASTNode::Unary(node) => format!("{}_{}", node.operator.token_type, depth),
-        ASTNode::Error => format!("ERROR_{}", depth),
+#[derive(Debug, Clone)]
+pub enum StmtNode {
+    Block(BlockNode),
+    Expression(ExpressionNode),
+    Function(FunctionNode),
+    If(IfNode),
+    Print(PrintNode),
+    Return(ReturnNode),
+    Var(VarNode),
+    While(WhileNode),
+}
+
+impl StmtNode {
+    /// Returns the handles of the expressions this statement holds directly.
+    pub fn expressions(&self) -> Vec<ExprId> {
+        match self {
+            StmtNode::Block(_) | StmtNode::Function(_) => vec![],
+            StmtNode::Expression(node) => vec![node.expression],
+            StmtNode::If(node) => vec![node.condition],
+            StmtNode::Print(node) => vec![node.expression],
+            StmtNode::Return(node) => node.value.into_iter().collect(),
+            StmtNode::Var(node) => node.initializer.into_iter().collect(),
+            StmtNode::While(node) => vec![node.condition],
+        }
+    }
+
+    /// Returns the handles of the statements nested directly in this one.
+    pub fn statements(&self) -> Vec<StmtId> {
+        match self {
+            StmtNode::Block(node) => node.statements.clone(),
+            StmtNode::Function(node) => vec![node.body],
+            StmtNode::If(node) => {
+                let mut statements = vec![node.then_branch];
+                statements.extend(node.else_branch);
+                statements
+            }
+            StmtNode::While(node) => vec![node.body],
+            _ => vec![],
+        }
     }
 }
 
-fn pretty_print_recursive(node: &ASTNode, acc: &mut String, depth: u32) {
-    let label = make_graphviz_label(node, depth);
-    match node {
-        ASTNode::Binary(bin_node) => {
-            acc.push_str(&format!(
-                "\"{}\"[label=\"{}\"];\n",
-                label, bin_node.operator.token_type
-            ));
+/// Flat, `Vec`-backed storage for a syntax tree.
+///
+/// Nodes refer to each other through `ExprId` and `StmtId` handles instead
+/// of owning their children, so building and dropping deep trees never
+/// recurses.
+#[derive(Debug, Default)]
+pub struct Ast {
+    nodes: Vec<ASTNode>,
+    spans: Vec<Span>,
+    parents: Vec<Option<NodeId>>,
+    stmts: Vec<StmtNode>,
+    stmt_spans: Vec<Span>,
+    stmt_parents: Vec<Option<StmtId>>,
+    statements: Vec<StmtId>,
+}
+
+impl Ast {
+    pub fn new() -> Self {
+        Self::default()
+    }
 
-            pretty_print_recursive(&bin_node.left, acc, depth + 1);
-            pretty_print_recursive(&bin_node.right, acc, depth + 1);
+    /// Moves `node` into the arena and returns its handle. Children of
+    /// `node` must already be in the arena; their parent is set to the new
+    /// node.
+    pub fn add(&mut self, node: ASTNode, span: Span) -> ExprId {
+        let id = ExprId(self.nodes.len());
+        for child in node.children() {
+            self.parents[child.0] = Some(NodeId::Expr(id));
+        }
+        self.nodes.push(node);
+        self.spans.push(span);
+        self.parents.push(None);
+        id
+    }
 
-            let label_left = make_graphviz_label(&bin_node.left, depth + 1);
-            let label_right = make_graphviz_label(&bin_node.right, depth + 1);
-            acc.push_str(&format!("\"{}\" -> \"{}\";\n", label, label_left));
-            acc.push_str(&format!("\"{}\" -> \"{}\";\n", label, label_right));
+    /// Moves the statement `node` into the arena and returns its handle.
+    /// Like `add`, it adopts the expressions and statements it contains.
+    pub fn add_stmt(&mut self, node: StmtNode, span: Span) -> StmtId {
+        let id = StmtId(self.stmts.len());
+        for child in node.expressions() {
+            self.parents[child.0] = Some(NodeId::Stmt(id));
+        }
+        for child in node.statements() {
+            self.stmt_parents[child.0] = Some(id);
         }
-        ASTNode::Grouping(group_node) => {
-            acc.push_str(&format!("\"{}\"[label=\"{}\"];\n", label, "()"));
-            pretty_print_recursive(&group_node.child, acc, depth + 1);
+        self.stmts.push(node);
+        self.stmt_spans.push(span);
+        self.stmt_parents.push(None);
+        id
+    }
+
+    pub fn get(&self, id: ExprId) -> &ASTNode {
+        &self.nodes[id.0]
+    }
+
+    pub fn get_stmt(&self, id: StmtId) -> &StmtNode {
+        &self.stmts[id.0]
+    }
+
+    pub fn span(&self, id: ExprId) -> Span {
+        self.spans[id.0]
+    }
+
+    pub fn stmt_span(&self, id: StmtId) -> Span {
+        self.stmt_spans[id.0]
+    }
 
-            let label_child = make_graphviz_label(&group_node.child, depth + 1);
-            acc.push_str(&format!("\"{}\" -> \"{}\";\n", label, label_child));
+    /// Overwrites the node stored at `id`, keeping its handle and parent.
+    /// The children of the old node stay in the arena, but detached: their
+    /// parent is cleared unless `node` adopts them again.
+    pub fn replace(&mut self, id: ExprId, node: ASTNode) {
+        for child in self.nodes[id.0].children() {
+            self.parents[child.0] = None;
         }
-        ASTNode::Literal(lit_node) => {
-            acc.push_str(&format!("\"{}\"[label=\"{}\"];\n", label, lit_node.value));
+        for child in node.children() {
+            self.parents[child.0] = Some(NodeId::Expr(id));
         }
-        ASTNode::Unary(unary_node) => {
-            acc.push_str(&format!(
-                "\"{}\"[label=\"{}\"];\n",
-                label, unary_node.operator.token_type
-            ));
-            pretty_print_recursive(&unary_node.child, acc, depth + 1);
+        self.nodes[id.0] = node;
+    }
 
-            let label_child = make_graphviz_label(&unary_node.child, depth + 1);
-            acc.push_str(&format!("\"{}\" -> \"{}\";\n", label, label_child));
+    /// Overwrites the statement stored at `id`, the way `replace` does for
+    /// expressions.
+    pub fn replace_stmt(&mut self, id: StmtId, node: StmtNode) {
+        for child in self.stmts[id.0].expressions() {
+            self.parents[child.0] = None;
+        }
+        for child in self.stmts[id.0].statements() {
+            self.stmt_parents[child.0] = None;
+        }
+        for child in node.expressions() {
+            self.parents[child.0] = Some(NodeId::Stmt(id));
         }
-        ASTNode::Error => {
-            acc.push_str(&format!("\"{}\"[label=\"{}\"];\n", label, "ERROR"));
+        for child in node.statements() {
+            self.stmt_parents[child.0] = Some(id);
         }
-    };
+        self.stmts[id.0] = node;
+    }
+
+    /// The expression or statement directly containing `id`.
+    pub fn parent(&self, id: ExprId) -> Option<NodeId> {
+        self.parents[id.0]
+    }
+
+    /// The statement directly containing `id`, or `None` at the top level.
+    pub fn stmt_parent(&self, id: StmtId) -> Option<StmtId> {
+        self.stmt_parents[id.0]
+    }
+
+    /// The top-level statements of the program, in source order.
+    pub fn statements(&self) -> &[StmtId] {
+        &self.statements
+    }
+
+    pub fn push_statement(&mut self, id: StmtId) {
+        self.statements.push(id);
+    }
 }
@@ -0,0 +1,321 @@
+use crate::ast::{
+    AssignNode, Ast, BinaryNode, BlockNode, CallNode, ConditionalNode, ExprId, ExpressionNode,
+    FunctionNode, GroupingNode, IfNode, IncrementNode, InterpolationNode, LiteralNode, PrintNode,
+    ReturnNode, StmtId, UnaryNode, VarNode, VariableNode, WhileNode,
+};
+use crate::json::JsonValue;
+use crate::token::{Position, Span, Token, TokenType};
+use crate::visitor::{self, Visitor};
+
+/// Version of the JSON layout produced by `to_json`. Bump it whenever a
+/// field is renamed or removed, or the meaning of an existing field changes.
+///
+/// Version 2 replaced the single `root` expression with a `statements` list.
+pub const SCHEMA_VERSION: f64 = 2.0;
+
+/// Serializes `ast` as a JSON document:
+///
+/// ```text
+/// { "schema": "rlox-ast", "version": 2, "statements": [<node>, ...] }
+/// ```
+///
+/// Every node is an object with a `kind`, a `span` and kind-specific
+/// fields holding its operator, name, value or children.
+pub fn to_json(ast: &Ast) -> String {
+    let mut builder = JsonBuilder { stack: Vec::new() };
+    builder.visit_program(ast);
+
+    JsonValue::object(vec![
+        ("schema", JsonValue::string("rlox-ast")),
+        ("version", JsonValue::Number(SCHEMA_VERSION)),
+        ("statements", JsonValue::Array(builder.stack)),
+    ])
+    .to_pretty_string()
+}
+
+fn position_json(position: Position) -> JsonValue {
+    JsonValue::object(vec![
+        ("line", JsonValue::Number(position.line as f64)),
+        ("column", JsonValue::Number(position.column as f64)),
+    ])
+}
+
+pub fn span_json(span: Span) -> JsonValue {
+    JsonValue::object(vec![
+        ("start", position_json(span.start)),
+        ("end", position_json(span.end)),
+    ])
+}
+
+/// Builds the JSON for each node after its children, using `stack` to hand
+/// finished children up to their parent.
+struct JsonBuilder {
+    stack: Vec<JsonValue>,
+}
+
+impl JsonBuilder {
+    fn pop(&mut self) -> JsonValue {
+        self.stack.pop().unwrap_or(JsonValue::Null)
+    }
+
+    /// Pops the values of the last `count` nodes, in the order they were
+    /// pushed.
+    fn pop_many(&mut self, count: usize) -> Vec<JsonValue> {
+        let at = self.stack.len().saturating_sub(count);
+        self.stack.split_off(at)
+    }
+
+    fn push_node(&mut self, ast: &Ast, id: ExprId, kind: &str, fields: Vec<(&str, JsonValue)>) {
+        self.push(kind, ast.span(id), fields);
+    }
+
+    fn push_stmt(&mut self, ast: &Ast, id: StmtId, kind: &str, fields: Vec<(&str, JsonValue)>) {
+        self.push(kind, ast.stmt_span(id), fields);
+    }
+
+    fn push(&mut self, kind: &str, span: Span, fields: Vec<(&str, JsonValue)>) {
+        let mut pairs = vec![("kind", JsonValue::string(kind)), ("span", span_json(span))];
+        pairs.extend(fields);
+        self.stack.push(JsonValue::object(pairs));
+    }
+}
+
+fn name_json(name: &Token) -> JsonValue {
+    JsonValue::String(name.token_type.to_string())
+}
+
+impl Visitor for JsonBuilder {
+    fn visit_block_stmt(&mut self, ast: &Ast, id: StmtId, node: &BlockNode) {
+        visitor::walk_block_stmt(self, ast, node);
+        let statements = self.pop_many(node.statements().len());
+        self.push_stmt(
+            ast,
+            id,
+            "Block",
+            vec![("statements", JsonValue::Array(statements))],
+        );
+    }
+
+    fn visit_expression_stmt(&mut self, ast: &Ast, id: StmtId, node: &ExpressionNode) {
+        self.visit_expr(ast, node.expression());
+        let expression = self.pop();
+        self.push_stmt(ast, id, "Expression", vec![("expression", expression)]);
+    }
+
+    fn visit_function_stmt(&mut self, ast: &Ast, id: StmtId, node: &FunctionNode) {
+        visitor::walk_function_stmt(self, ast, node);
+        let body = self.pop();
+        let params = node.params().iter().map(name_json).collect();
+        self.push_stmt(
+            ast,
+            id,
+            "Function",
+            vec![
+                ("name", name_json(node.name())),
+                ("params", JsonValue::Array(params)),
+                ("body", body),
+            ],
+        );
+    }
+
+    fn visit_if_stmt(&mut self, ast: &Ast, id: StmtId, node: &IfNode) {
+        visitor::walk_if_stmt(self, ast, node);
+        let else_branch = match node.else_branch() {
+            Some(_) => self.pop(),
+            None => JsonValue::Null,
+        };
+        let then_branch = self.pop();
+        let condition = self.pop();
+        self.push_stmt(
+            ast,
+            id,
+            "If",
+            vec![
+                ("condition", condition),
+                ("then", then_branch),
+                ("else", else_branch),
+            ],
+        );
+    }
+
+    fn visit_return_stmt(&mut self, ast: &Ast, id: StmtId, node: &ReturnNode) {
+        visitor::walk_return_stmt(self, ast, node);
+        let value = match node.value() {
+            Some(_) => self.pop(),
+            None => JsonValue::Null,
+        };
+        self.push_stmt(ast, id, "Return", vec![("value", value)]);
+    }
+
+    fn visit_while_stmt(&mut self, ast: &Ast, id: StmtId, node: &WhileNode) {
+        visitor::walk_while_stmt(self, ast, node);
+        let body = self.pop();
+        let condition = self.pop();
+        self.push_stmt(
+            ast,
+            id,
+            "While",
+            vec![("condition", condition), ("body", body)],
+        );
+    }
+
+    fn visit_print_stmt(&mut self, ast: &Ast, id: StmtId, node: &PrintNode) {
+        self.visit_expr(ast, node.expression());
+        let expression = self.pop();
+        self.push_stmt(ast, id, "Print", vec![("expression", expression)]);
+    }
+
+    fn visit_var_stmt(&mut self, ast: &Ast, id: StmtId, node: &VarNode) {
+        visitor::walk_var_stmt(self, ast, node);
+        let initializer = match node.initializer() {
+            Some(_) => self.pop(),
+            None => JsonValue::Null,
+        };
+        self.push_stmt(
+            ast,
+            id,
+            "Var",
+            vec![
+                ("name", name_json(node.name())),
+                ("initializer", initializer),
+            ],
+        );
+    }
+
+    fn visit_assign(&mut self, ast: &Ast, id: ExprId, node: &AssignNode) {
+        self.visit_expr(ast, node.value());
+        let value = self.pop();
+        self.push_node(
+            ast,
+            id,
+            "Assign",
+            vec![
+                ("name", name_json(node.name())),
+                (
+                    "operator",
+                    JsonValue::String(node.operator().token_type.to_string()),
+                ),
+                ("value", value),
+            ],
+        );
+    }
+
+    fn visit_variable(&mut self, ast: &Ast, id: ExprId, node: &VariableNode) {
+        self.push_node(ast, id, "Variable", vec![("name", name_json(node.name()))]);
+    }
+
+    fn visit_binary(&mut self, ast: &Ast, id: ExprId, node: &BinaryNode) {
+        visitor::walk_binary(self, ast, node);
+        let right = self.pop();
+        let left = self.pop();
+        let operator = node.operator().token_type.to_string();
+        self.push_node(
+            ast,
+            id,
+            "Binary",
+            vec![
+                ("operator", JsonValue::String(operator)),
+                ("left", left),
+                ("right", right),
+            ],
+        );
+    }
+
+    fn visit_call(&mut self, ast: &Ast, id: ExprId, node: &CallNode) {
+        visitor::walk_call(self, ast, node);
+        let arguments = self.pop_many(node.arguments().len());
+        let callee = self.pop();
+        self.push_node(
+            ast,
+            id,
+            "Call",
+            vec![
+                ("callee", callee),
+                ("arguments", JsonValue::Array(arguments)),
+            ],
+        );
+    }
+
+    fn visit_conditional(&mut self, ast: &Ast, id: ExprId, node: &ConditionalNode) {
+        visitor::walk_conditional(self, ast, node);
+        let else_branch = self.pop();
+        let then_branch = self.pop();
+        let condition = self.pop();
+        self.push_node(
+            ast,
+            id,
+            "Conditional",
+            vec![
+                ("condition", condition),
+                ("then", then_branch),
+                ("else", else_branch),
+            ],
+        );
+    }
+
+    fn visit_grouping(&mut self, ast: &Ast, id: ExprId, node: &GroupingNode) {
+        visitor::walk_grouping(self, ast, node);
+        let expression = self.pop();
+        self.push_node(ast, id, "Grouping", vec![("expression", expression)]);
+    }
+
+    fn visit_increment(&mut self, ast: &Ast, id: ExprId, node: &IncrementNode) {
+        let operator = node.operator().token_type.to_string();
+        self.push_node(
+            ast,
+            id,
+            "Increment",
+            vec![
+                ("operator", JsonValue::String(operator)),
+                ("name", name_json(node.name())),
+                ("prefix", JsonValue::Bool(node.is_prefix())),
+            ],
+        );
+    }
+
+    fn visit_interpolation(&mut self, ast: &Ast, id: ExprId, node: &InterpolationNode) {
+        visitor::walk_interpolation(self, ast, node);
+        let parts = self.pop_many(node.parts().len());
+        self.push_node(
+            ast,
+            id,
+            "Interpolation",
+            vec![("parts", JsonValue::Array(parts))],
+        );
+    }
+
+    fn visit_literal(&mut self, ast: &Ast, id: ExprId, node: &LiteralNode) {
+        let (literal_type, value) = match node.value() {
+            TokenType::Number(n) => ("number", JsonValue::Number(*n)),
+            TokenType::String(s) => ("string", JsonValue::string(s)),
+            TokenType::True => ("boolean", JsonValue::Bool(true)),
+            TokenType::False => ("boolean", JsonValue::Bool(false)),
+            _ => ("nil", JsonValue::Null),
+        };
+        self.push_node(
+            ast,
+            id,
+            "Literal",
+            vec![("type", JsonValue::string(literal_type)), ("value", value)],
+        );
+    }
+
+    fn visit_unary(&mut self, ast: &Ast, id: ExprId, node: &UnaryNode) {
+        visitor::walk_unary(self, ast, node);
+        let operand = self.pop();
+        let operator = node.operator().token_type.to_string();
+        self.push_node(
+            ast,
+            id,
+            "Unary",
+            vec![
+                ("operator", JsonValue::String(operator)),
+                ("operand", operand),
+            ],
+        );
+    }
+
+    fn visit_error(&mut self, ast: &Ast, id: ExprId) {
+        self.push_node(ast, id, "Error", vec![]);
+    }
+}
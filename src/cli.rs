@@ -0,0 +1,367 @@
+use crate::formatter;
+use crate::interpreter::Emit;
+
+use std::mem;
+
+pub const USAGE: &str = "\
+Usage: rlox [options] [script [args...]]
+       rlox run <script> [-- args...]
+       rlox repl
+       rlox check <script>
+       rlox lint <script>
+       rlox fmt [--check] [--width=<columns>] <script> | --stdin
+       rlox -e <code> [-- args...]
+
+A script path of - reads the program from standard input, as does running
+rlox with no script when standard input is not a terminal.
+
+Options:
+  -e <code>        run <code> instead of a script
+  -O               optimize the program before running it
+  --emit=<kind>    print dot, sexpr, ast-json, cst, optimized-ast, tokens
+                   or tokens-json instead of running the program
+  -h, --help       show this message
+  -V, --version    show the version
+
+Formatter options:
+  --check          only report whether the script is formatted
+  --stdin          format standard input to standard output
+  --width=<n>      wrap lines longer than <n> columns (default 80)";
+
+/// What `rlox` was asked to do.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// Run a script. `args` are the arguments meant for the script itself.
+    Run {
+        path: String,
+        args: Vec<String>,
+    },
+    /// Run source given on the command line with `-e`.
+    Eval {
+        code: String,
+        args: Vec<String>,
+    },
+    /// No command or script was given: start the REPL, or run standard
+    /// input as a script when it is not a terminal.
+    Default,
+    Repl,
+    /// Scan and parse a script without running it.
+    Check {
+        path: String,
+    },
+    /// Report lint diagnostics for a script without running it.
+    Lint {
+        path: String,
+    },
+    /// Format a script in place, or standard input to standard output
+    /// when `path` is `-`.
+    Fmt {
+        path: String,
+        check: bool,
+        width: usize,
+    },
+    Help,
+    Version,
+}
+
+#[derive(Debug)]
+pub struct Options {
+    pub command: Command,
+    pub emit: Option<Emit>,
+    /// Run the optimizer over the AST first.
+    pub optimize: bool,
+}
+
+/// Parses the command line, not including the program name. Returns a
+/// message suitable for printing above the usage text on failure.
+///
+/// Options may appear anywhere before the script path, and `--` ends them,
+/// so `rlox -- -a.lox` runs a script whose name starts with a dash.
+/// Everything after the path of a script to run belongs to the script, so
+/// `rlox run a.lox -- -x` and `rlox a.lox -x` both pass `-x` along.
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Options, String> {
+    let mut args = args.into_iter();
+    let mut emit = None;
+    let mut code = None;
+    let mut optimize = false;
+    let mut positional: Vec<String> = Vec::new();
+    let mut script_args: Vec<String> = Vec::new();
+    let mut check = false;
+    let mut stdin = false;
+    let mut width = None;
+
+    // Set by `--`, after which no argument is an option.
+    let mut options_ended = false;
+
+    while let Some(arg) = args.next() {
+        if positional_is_script(&positional) {
+            // A `--` right after the script path only separates it from the
+            // script's own arguments.
+            if arg == "--" && !options_ended && script_args.is_empty() {
+                options_ended = true;
+            } else {
+                script_args.push(arg);
+            }
+            continue;
+        }
+        if options_ended {
+            positional.push(arg);
+            continue;
+        }
+
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(options(Command::Help, None, false)),
+            "-V" | "--version" => return Ok(options(Command::Version, None, false)),
+            "-O" => optimize = true,
+            "-e" => match args.next() {
+                Some(src) => code = Some(src),
+                None => return Err("-e expects an argument".to_string()),
+            },
+            "--" if code.is_some() => script_args.extend(args.by_ref()),
+            "--" => options_ended = true,
+            "--check" => check = true,
+            "--stdin" => stdin = true,
+            _ => {
+                if let Some(kind) = arg.strip_prefix("--emit=") {
+                    emit = Some(kind.parse()?);
+                } else if let Some(columns) = arg.strip_prefix("--width=") {
+                    match columns.parse() {
+                        Ok(columns) if columns > 0 => width = Some(columns),
+                        _ => return Err(format!("invalid width: {}", columns)),
+                    }
+                } else if arg.starts_with('-') && arg != "-" {
+                    return Err(format!("unknown option {}", arg));
+                } else {
+                    positional.push(arg);
+                }
+            }
+        }
+    }
+
+    if code.is_some() && !positional.is_empty() {
+        return Err("-e cannot be combined with a script".to_string());
+    }
+
+    let mut positional = positional.into_iter();
+    let command = match (code, positional.next().as_deref()) {
+        (Some(code), _) => Command::Eval {
+            code,
+            args: mem::take(&mut script_args),
+        },
+        (None, None) => Command::Default,
+        (None, Some("repl")) => Command::Repl,
+        (None, Some("run")) => Command::Run {
+            path: expect_path("run", positional.next())?,
+            args: mem::take(&mut script_args),
+        },
+        (None, Some("check")) => Command::Check {
+            path: expect_path("check", positional.next())?,
+        },
+        (None, Some("lint")) => Command::Lint {
+            path: expect_path("lint", positional.next())?,
+        },
+        (None, Some("fmt")) => Command::Fmt {
+            path: match (stdin, positional.next()) {
+                (true, None) => "-".to_string(),
+                (true, Some(_)) => {
+                    return Err("--stdin cannot be combined with a script".to_string())
+                }
+                (false, path) => expect_path("fmt", path)?,
+            },
+            check,
+            width: width.unwrap_or(formatter::DEFAULT_WIDTH),
+        },
+        (None, Some(path)) => Command::Run {
+            path: path.to_string(),
+            args: mem::take(&mut script_args),
+        },
+    };
+
+    let is_fmt = matches!(command, Command::Fmt { .. });
+    if !is_fmt && (check || stdin || width.is_some()) {
+        return Err("--check, --stdin and --width only apply to fmt".to_string());
+    }
+    let runs_program = matches!(
+        command,
+        Command::Run { .. } | Command::Eval { .. } | Command::Default
+    );
+    if !runs_program && (emit.is_some() || optimize) {
+        return Err("--emit and -O only apply to running a program".to_string());
+    }
+
+    // Only `run` passes arguments on to the script.
+    match positional.next().or_else(|| script_args.pop()) {
+        Some(extra) => Err(format!("unexpected argument {}", extra)),
+        None => Ok(options(command, emit, optimize)),
+    }
+}
+
+fn options(command: Command, emit: Option<Emit>, optimize: bool) -> Options {
+    Options {
+        command,
+        emit,
+        optimize,
+    }
+}
+
+/// True once `positional` holds a script path, after which the remaining
+/// arguments are the script's own.
+fn positional_is_script(positional: &[String]) -> bool {
+    match positional.first().map(String::as_str) {
+        None => false,
+        Some("run") => positional.len() > 1,
+        Some("check") | Some("lint") | Some("fmt") | Some("repl") => false,
+        Some(_) => true,
+    }
+}
+
+fn expect_path(command: &str, path: Option<String>) -> Result<String, String> {
+    path.ok_or_else(|| format!("{} expects a script path", command))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    fn run(path: &str, args: &[&str]) -> Command {
+        Command::Run {
+            path: path.to_string(),
+            args: strings(args),
+        }
+    }
+
+    fn fmt(path: &str, check: bool, width: usize) -> Command {
+        Command::Fmt {
+            path: path.to_string(),
+            check,
+            width,
+        }
+    }
+
+    #[test]
+    fn commands() {
+        let eval = |code: &str, args: &[&str]| Command::Eval {
+            code: code.to_string(),
+            args: strings(args),
+        };
+        let path = |path: &str| path.to_string();
+        let table: &[(&[&str], Command)] = &[
+            (&[], Command::Default),
+            (&["repl"], Command::Repl),
+            (&["a.lox"], run("a.lox", &[])),
+            (&["-"], run("-", &[])),
+            (&["run", "a.lox"], run("a.lox", &[])),
+            (&["run", "repl"], run("repl", &[])),
+            (
+                &["check", "a.lox"],
+                Command::Check {
+                    path: path("a.lox"),
+                },
+            ),
+            (
+                &["lint", "a.lox"],
+                Command::Lint {
+                    path: path("a.lox"),
+                },
+            ),
+            (&["fmt", "a.lox"], fmt("a.lox", false, 80)),
+            (&["fmt", "--stdin"], fmt("-", false, 80)),
+            (
+                &["fmt", "--check", "--width=100", "a.lox"],
+                fmt("a.lox", true, 100),
+            ),
+            (&["--check", "fmt", "a.lox"], fmt("a.lox", true, 80)),
+            (&["-e", "print 1;"], eval("print 1;", &[])),
+            (&["-e", "print 1;", "--", "x"], eval("print 1;", &["x"])),
+            (&["-h"], Command::Help),
+            (&["run", "a.lox", "--help"], run("a.lox", &["--help"])),
+            (&["--version"], Command::Version),
+        ];
+        for (args, command) in table {
+            assert_eq!(&parse(args).unwrap().command, command, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn arguments_after_the_script_are_passed_on() {
+        let table: &[(&[&str], Command)] = &[
+            (&["a.lox", "-x", "y"], run("a.lox", &["-x", "y"])),
+            (&["a.lox", "--", "-x"], run("a.lox", &["-x"])),
+            (&["a.lox", "--", "--"], run("a.lox", &["--"])),
+            (&["run", "a.lox", "--", "-O"], run("a.lox", &["-O"])),
+            (&["run", "--", "-a.lox", "b"], run("-a.lox", &["b"])),
+            (&["-O", "--", "-a.lox", "b"], run("-a.lox", &["b"])),
+            (&["--", "a.lox", "--", "b"], run("a.lox", &["--", "b"])),
+        ];
+        for (args, command) in table {
+            assert_eq!(&parse(args).unwrap().command, command, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn emit_and_optimize() {
+        let options = parse(&["-O", "--emit=dot", "a.lox"]).unwrap();
+        assert_eq!(options.command, run("a.lox", &[]));
+        assert_eq!(options.emit, Some(Emit::Dot));
+        assert!(options.optimize);
+
+        let options = parse(&["-e", "1;", "--emit=optimized-ast"]).unwrap();
+        assert_eq!(options.emit, Some(Emit::OptimizedAst));
+        assert!(!options.optimize);
+
+        // Without a script, standard input may still be a program.
+        assert!(parse(&["-O"]).is_ok());
+    }
+
+    #[test]
+    fn options_each_command_rejects() {
+        let table: &[(&[&str], &str)] = &[
+            (&["--check", "a.lox"], "only apply to fmt"),
+            (&["--stdin"], "only apply to fmt"),
+            (
+                &["-e", "print 1;", "--stdin", "--width=5", "--check"],
+                "only apply to fmt",
+            ),
+            (&["lint", "--width=5", "a.lox"], "only apply to fmt"),
+            (&["repl", "--check"], "only apply to fmt"),
+            (&["check", "--emit=dot", "a.lox"], "only apply to running"),
+            (&["lint", "-O", "a.lox"], "only apply to running"),
+            (&["fmt", "-O", "a.lox"], "only apply to running"),
+            (&["repl", "--emit=sexpr"], "only apply to running"),
+            (&["fmt", "--stdin", "a.lox"], "cannot be combined"),
+            (&["-e", "1;", "a.lox"], "cannot be combined"),
+        ];
+        for (args, message) in table {
+            let err = parse(args).unwrap_err();
+            assert!(err.contains(message), "{:?} gave {:?}", args, err);
+        }
+    }
+
+    #[test]
+    fn malformed_command_lines() {
+        let table: &[(&[&str], &str)] = &[
+            (&["-e"], "-e expects an argument"),
+            (&["--frobnicate"], "unknown option --frobnicate"),
+            (&["--emit=png", "a.lox"], "unknown --emit kind: png"),
+            (&["fmt", "--width=0", "a.lox"], "invalid width: 0"),
+            (&["fmt", "--width=wide", "a.lox"], "invalid width: wide"),
+            (&["run"], "run expects a script path"),
+            (&["check"], "check expects a script path"),
+            (&["fmt"], "fmt expects a script path"),
+            (&["check", "a.lox", "b.lox"], "unexpected argument b.lox"),
+            (&["repl", "a.lox"], "unexpected argument a.lox"),
+            (&["lint", "a.lox", "--", "x"], "unexpected argument x"),
+        ];
+        for (args, message) in table {
+            assert_eq!(&parse(args).unwrap_err(), message, "{:?}", args);
+        }
+    }
+}
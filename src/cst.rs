@@ -0,0 +1,293 @@
+use crate::ast::{ASTNode, Ast, NodeId, StmtNode};
+use crate::error::{RLoxError, RLoxResult};
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::token::{Span, Token};
+
+use std::iter::Peekable;
+use std::vec::IntoIter;
+
+/// Kind of a `SyntaxNode`, after the AST node it wraps.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyntaxKind {
+    Program,
+    Block,
+    Expression,
+    Function,
+    If,
+    Print,
+    Return,
+    Var,
+    While,
+    Assign,
+    Binary,
+    Call,
+    Conditional,
+    Grouping,
+    Increment,
+    Interpolation,
+    Literal,
+    Unary,
+    Variable,
+    Error,
+}
+
+#[derive(Debug)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(Token),
+}
+
+/// A node of a concrete syntax tree. Unlike an `Ast` node it owns every
+/// token it was parsed from, trivia included, in source order.
+#[derive(Debug)]
+pub struct SyntaxNode {
+    kind: SyntaxKind,
+    id: Option<NodeId>,
+    children: Vec<SyntaxElement>,
+}
+
+impl SyntaxNode {
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    /// The AST node this node wraps. Only the `Program` root has none.
+    pub fn id(&self) -> Option<NodeId> {
+        self.id
+    }
+
+    pub fn children(&self) -> &[SyntaxElement] {
+        &self.children
+    }
+
+    /// The source text this node was parsed from.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        self.push_text(&mut text);
+        text
+    }
+
+    fn push_text(&self, text: &mut String) {
+        for child in &self.children {
+            match child {
+                SyntaxElement::Node(node) => node.push_text(text),
+                SyntaxElement::Token(token) => text.push_str(token.lexeme.as_deref().unwrap_or("")),
+            }
+        }
+    }
+}
+
+/// A lossless syntax tree for a whole program, with the `Ast` it was built
+/// from as a typed view of its nodes.
+///
+/// `root().text()` gives back the source exactly, comments and whitespace
+/// included, which is what tools that rewrite source need.
+#[derive(Debug)]
+pub struct Cst {
+    ast: Ast,
+    root: SyntaxNode,
+}
+
+impl Cst {
+    /// Scans `src` in lossless mode, parses the significant tokens, and
+    /// hangs every token, trivia included, under the innermost AST node
+    /// whose span contains it.
+    pub fn parse(src: String) -> RLoxResult<Cst> {
+        let tokens = Scanner::new(src.clone())
+            .lossless()
+            .collect::<RLoxResult<Vec<Token>>>()?;
+        let significant = tokens
+            .iter()
+            .filter(|token| !token.token_type.is_trivia())
+            .cloned()
+            .collect();
+        let ast = Parser::new(significant).parse()?;
+
+        let mut builder = Builder {
+            ast: &ast,
+            tokens: tokens.into_iter().peekable(),
+        };
+        let statements = ast.statements().iter().map(|&s| NodeId::Stmt(s)).collect();
+        let root = builder.node(SyntaxKind::Program, None, statements, usize::MAX);
+
+        if root.text() != src {
+            return Err(RLoxError::source(
+                None,
+                None,
+                "syntax tree does not reproduce the source".to_string(),
+            ));
+        }
+        Ok(Cst { ast, root })
+    }
+
+    pub fn ast(&self) -> &Ast {
+        &self.ast
+    }
+
+    pub fn root(&self) -> &SyntaxNode {
+        &self.root
+    }
+}
+
+struct Builder<'a> {
+    ast: &'a Ast,
+    tokens: Peekable<IntoIter<Token>>,
+}
+
+impl Builder<'_> {
+    fn build(&mut self, id: NodeId) -> SyntaxNode {
+        let (kind, mut children) = match id {
+            NodeId::Stmt(stmt) => {
+                let node = self.ast.get_stmt(stmt);
+                let mut children: Vec<NodeId> =
+                    node.expressions().into_iter().map(NodeId::Expr).collect();
+                children.extend(node.statements().into_iter().map(NodeId::Stmt));
+                (stmt_kind(node), children)
+            }
+            NodeId::Expr(expr) => {
+                let node = self.ast.get(expr);
+                let children = node.children().into_iter().map(NodeId::Expr).collect();
+                (expr_kind(node), children)
+            }
+        };
+        children.sort_by_key(|&child| self.span(child).start.offset);
+        self.node(kind, Some(id), children, self.span(id).end.offset)
+    }
+
+    /// Builds a node from `children` and the tokens around them, taking
+    /// tokens up to the character offset `end`.
+    fn node(
+        &mut self,
+        kind: SyntaxKind,
+        id: Option<NodeId>,
+        children: Vec<NodeId>,
+        end: usize,
+    ) -> SyntaxNode {
+        let mut elements = Vec::new();
+        for child in children {
+            self.take_tokens(&mut elements, self.span(child).start.offset);
+            elements.push(SyntaxElement::Node(self.build(child)));
+        }
+        self.take_tokens(&mut elements, end);
+
+        SyntaxNode {
+            kind,
+            id,
+            children: elements,
+        }
+    }
+
+    fn take_tokens(&mut self, elements: &mut Vec<SyntaxElement>, end: usize) {
+        while let Some(token) = self.tokens.next_if(|t| t.span.start.offset < end) {
+            elements.push(SyntaxElement::Token(token));
+        }
+    }
+
+    fn span(&self, id: NodeId) -> Span {
+        match id {
+            NodeId::Expr(id) => self.ast.span(id),
+            NodeId::Stmt(id) => self.ast.stmt_span(id),
+        }
+    }
+}
+
+fn stmt_kind(node: &StmtNode) -> SyntaxKind {
+    match node {
+        StmtNode::Block(_) => SyntaxKind::Block,
+        StmtNode::Expression(_) => SyntaxKind::Expression,
+        StmtNode::Function(_) => SyntaxKind::Function,
+        StmtNode::If(_) => SyntaxKind::If,
+        StmtNode::Print(_) => SyntaxKind::Print,
+        StmtNode::Return(_) => SyntaxKind::Return,
+        StmtNode::Var(_) => SyntaxKind::Var,
+        StmtNode::While(_) => SyntaxKind::While,
+    }
+}
+
+fn expr_kind(node: &ASTNode) -> SyntaxKind {
+    match node {
+        ASTNode::Assign(_) => SyntaxKind::Assign,
+        ASTNode::Binary(_) => SyntaxKind::Binary,
+        ASTNode::Call(_) => SyntaxKind::Call,
+        ASTNode::Conditional(_) => SyntaxKind::Conditional,
+        ASTNode::Grouping(_) => SyntaxKind::Grouping,
+        ASTNode::Increment(_) => SyntaxKind::Increment,
+        ASTNode::Interpolation(_) => SyntaxKind::Interpolation,
+        ASTNode::Literal(_) => SyntaxKind::Literal,
+        ASTNode::Unary(_) => SyntaxKind::Unary,
+        ASTNode::Variable(_) => SyntaxKind::Variable,
+        ASTNode::Error => SyntaxKind::Error,
+    }
+}
+
+/// Renders `cst` as an indented outline, one node or token per line. Nodes
+/// show the span of their AST node and tokens their quoted lexeme.
+pub fn to_tree(cst: &Cst) -> String {
+    let mut lines = Vec::new();
+    push_tree(&mut lines, cst.ast(), cst.root(), 0);
+    lines.join("\n")
+}
+
+fn push_tree(lines: &mut Vec<String>, ast: &Ast, node: &SyntaxNode, depth: usize) {
+    let span = match node.id() {
+        Some(NodeId::Expr(id)) => format!(" {}", ast.span(id)),
+        Some(NodeId::Stmt(id)) => format!(" {}", ast.stmt_span(id)),
+        None => String::new(),
+    };
+    lines.push(format!("{}{:?}{}", "  ".repeat(depth), node.kind(), span));
+    for child in node.children() {
+        match child {
+            SyntaxElement::Node(child) => push_tree(lines, ast, child, depth + 1),
+            SyntaxElement::Token(token) => lines.push(format!(
+                "{}{} {:?}",
+                "  ".repeat(depth + 1),
+                token.token_type.name(),
+                token.lexeme.as_deref().unwrap_or("")
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_the_source() {
+        let sources = [
+            "",
+            "print 1;",
+            "  var   x=1 ;\n\n\n// trailing\n",
+            "{ /* a /* nested */ b */ var y = x ** 2; }\n",
+            "print \"a${ 1 +2 }b\" ;\t// c\n",
+            "var z = (1 ? 2 : 3);\r\nz, ++z;\r\n",
+            "x += 1; z--; print -(-x) % 3;",
+            "fun f(a, /* b */ c) {\n  if (a) return c; // d\n  else while (c) c--;\n}\n",
+        ];
+        for src in &sources {
+            let cst = Cst::parse(src.to_string()).unwrap();
+            assert_eq!(cst.root().text(), *src);
+        }
+    }
+
+    #[test]
+    fn nodes_follow_the_statements() {
+        let cst = Cst::parse("print 1 + 2;\n{ x; }".to_string()).unwrap();
+        let kinds: Vec<SyntaxKind> = cst
+            .root()
+            .children()
+            .iter()
+            .filter_map(|element| match element {
+                SyntaxElement::Node(node) => Some(node.kind()),
+                SyntaxElement::Token(_) => None,
+            })
+            .collect();
+        assert_eq!(kinds, vec![SyntaxKind::Print, SyntaxKind::Block]);
+    }
+
+    #[test]
+    fn syntax_errors_are_reported() {
+        assert!(Cst::parse("print (1;".to_string()).is_err());
+    }
+}
@@ -0,0 +1,87 @@
+use crate::value::Value;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+type Scope = Rc<RefCell<HashMap<String, Value>>>;
+
+/// Variable bindings, as a stack of scopes. The bottom scope holds the
+/// globals and is never popped.
+///
+/// Scopes are shared, so a closure made with `capture` sees later changes
+/// to the variables it captured, and the enclosing code sees its changes.
+#[derive(Debug)]
+pub struct Environment {
+    scopes: Vec<Scope>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Environment {
+            scopes: vec![Scope::default()],
+        }
+    }
+
+    /// An environment sharing every scope of this one, for a function
+    /// declared here to run in.
+    pub fn capture(&self) -> Self {
+        Environment {
+            scopes: self.scopes.clone(),
+        }
+    }
+
+    pub fn push_scope(&mut self) {
+        self.scopes.push(Scope::default());
+    }
+
+    pub fn pop_scope(&mut self) {
+        if self.scopes.len() > 1 {
+            self.scopes.pop();
+        }
+    }
+
+    /// Binds `name` in the innermost scope, replacing any earlier binding
+    /// of the same name in that scope.
+    pub fn define(&mut self, name: &str, value: Value) {
+        if let Some(scope) = self.scopes.last() {
+            scope.borrow_mut().insert(name.to_string(), value);
+        }
+    }
+
+    /// Bindings in the global scope, sorted by name.
+    pub fn globals(&self) -> Vec<(String, Value)> {
+        let mut globals: Vec<(String, Value)> = self.scopes[0]
+            .borrow()
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect();
+        globals.sort_by(|a, b| a.0.cmp(&b.0));
+        globals
+    }
+
+    /// Looks `name` up from the innermost scope outwards.
+    pub fn get(&self, name: &str) -> Option<Value> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.borrow().get(name).cloned())
+    }
+
+    /// Updates the innermost existing binding of `name`. Returns false if
+    /// `name` is not bound in any scope.
+    pub fn assign(&mut self, name: &str, value: Value) -> bool {
+        match self
+            .scopes
+            .iter()
+            .rev()
+            .find(|scope| scope.borrow().contains_key(name))
+        {
+            Some(scope) => {
+                scope.borrow_mut().insert(name.to_string(), value);
+                true
+            }
+            None => false,
+        }
+    }
+}
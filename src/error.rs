@@ -1,17 +1,64 @@
+use crate::value::Value;
+
 use rustyline::error::ReadlineError;
 use std::convert;
-use std::io::ErrorKind;
+use std::fmt;
+use std::io::{self, ErrorKind};
+use std::path::PathBuf;
+
+/// What the interpreter was doing when an IO error happened.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IoOperation {
+    Read,
+    Write,
+    CreateDir,
+}
+
+impl fmt::Display for IoOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IoOperation::Read => write!(f, "read"),
+            IoOperation::Write => write!(f, "write"),
+            IoOperation::CreateDir => write!(f, "create directory"),
+        }
+    }
+}
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug)]
 pub enum RLoxError {
     IO {
-        kind: std::io::ErrorKind,
+        operation: IoOperation,
+        /// The file involved, or `None` for standard input and the
+        /// terminal.
+        path: Option<PathBuf>,
+        source: io::Error,
     },
     Source {
         line: Option<usize>,
         context: Option<String>,
         message: String,
     },
+    /// The source ended while a construct was still open, such as an
+    /// unclosed `(` or string. The REPL uses this to ask for more input.
+    UnexpectedEOF {
+        line: Option<usize>,
+        message: String,
+    },
+    Runtime {
+        line: Option<usize>,
+        message: String,
+    },
+    /// The program called `exit(code)`.
+    Exit {
+        code: i32,
+    },
+    /// A `return` statement, unwinding to the call it returns from. The
+    /// parser rejects `return` outside a function, so it never gets
+    /// further than that.
+    Return {
+        value: Value,
+    },
     Interrupted,
     EOF,
 }
@@ -24,35 +71,111 @@ impl RLoxError {
             message,
         }
     }
+
+    pub fn runtime(line: Option<usize>, message: String) -> Self {
+        RLoxError::Runtime { line, message }
+    }
+
+    pub fn io<P: Into<PathBuf>>(
+        operation: IoOperation,
+        path: Option<P>,
+        source: io::Error,
+    ) -> Self {
+        RLoxError::IO {
+            operation,
+            path: path.map(Into::into),
+            source,
+        }
+    }
+
+    /// The process exit code for this error, following the BSD sysexits
+    /// conventions where one applies.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            // EX_NOINPUT: the program to run could not be opened.
+            RLoxError::IO {
+                operation: IoOperation::Read,
+                path: Some(_),
+                source,
+            } if matches!(
+                source.kind(),
+                ErrorKind::NotFound | ErrorKind::PermissionDenied
+            ) =>
+            {
+                66
+            }
+            // EX_DATAERR: the input is not UTF-8.
+            RLoxError::IO { source, .. } if source.kind() == ErrorKind::InvalidData => 65,
+            // EX_IOERR
+            RLoxError::IO { .. } => 74,
+            // EX_DATAERR
+            RLoxError::Source { .. } | RLoxError::UnexpectedEOF { .. } => 65,
+            // EX_SOFTWARE
+            RLoxError::Runtime { .. } => 70,
+            RLoxError::Exit { code } => *code,
+            RLoxError::Return { .. } => 0,
+            RLoxError::Interrupted => 1,
+            RLoxError::EOF => 0,
+        }
+    }
 }
 
 impl std::fmt::Display for RLoxError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            RLoxError::IO { kind } => write!(f, "IO error: {:?}", kind),
+            RLoxError::IO {
+                operation,
+                path: Some(path),
+                source,
+            } => write!(f, "cannot {} {}: {}", operation, path.display(), source),
+            RLoxError::IO {
+                operation,
+                path: None,
+                source,
+            } => write!(f, "cannot {} input: {}", operation, source),
             RLoxError::Source {
                 line,
                 context,
                 message,
-            } => write!(
-                f,
-                "[{}] Error {}: {}",
-                line.unwrap_or(0),
-                context.clone().unwrap_or("".to_string()),
-                message
-            ),
+            } => write_diagnostic(f, *line, "Error", context.as_deref(), message),
+            RLoxError::UnexpectedEOF { line, message } => {
+                write!(f, "[{}] Error at end: {}", line.unwrap_or(0), message)
+            }
+            RLoxError::Runtime { line, message } => {
+                write!(f, "[{}] Runtime error: {}", line.unwrap_or(0), message)
+            }
+            RLoxError::Exit { code } => write!(f, "Exited with code {}", code),
+            RLoxError::Return { value } => write!(f, "Returned {}", value),
             RLoxError::Interrupted => write!(f, "Interrupted"),
             RLoxError::EOF => write!(f, "End  of input"),
         }
     }
 }
 
-impl std::error::Error for RLoxError {}
+/// Writes a diagnostic in the `[line] Label context: message` form shared
+/// by syntax errors and lints.
+pub fn write_diagnostic(
+    f: &mut fmt::Formatter<'_>,
+    line: Option<usize>,
+    label: &str,
+    context: Option<&str>,
+    message: &str,
+) -> fmt::Result {
+    write!(
+        f,
+        "[{}] {} {}: {}",
+        line.unwrap_or(0),
+        label,
+        context.unwrap_or(""),
+        message
+    )
+}
 
-impl convert::From<std::io::Error> for RLoxError {
-    fn from(io_error: std::io::Error) -> Self {
-        RLoxError::IO {
-            kind: io_error.kind(),
+impl std::error::Error for RLoxError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RLoxError::IO { source, .. } => Some(source),
+            _ => None,
         }
     }
 }
@@ -62,9 +185,12 @@ impl convert::From<ReadlineError> for RLoxError {
         match error {
             ReadlineError::Interrupted => RLoxError::Interrupted,
             ReadlineError::Eof => RLoxError::EOF,
-            _ => RLoxError::IO {
-                kind: ErrorKind::Other,
-            },
+            ReadlineError::Io(source) => RLoxError::io(IoOperation::Read, None::<PathBuf>, source),
+            error => RLoxError::io(
+                IoOperation::Read,
+                None::<PathBuf>,
+                io::Error::other(error.to_string()),
+            ),
         }
     }
 }
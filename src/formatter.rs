@@ -0,0 +1,610 @@
+use crate::ast::NodeId;
+use crate::cst::{Cst, SyntaxElement, SyntaxKind, SyntaxNode};
+use crate::error::{IoOperation, RLoxError, RLoxResult};
+use crate::interpreter;
+use crate::scanner::Scanner;
+use crate::token::{Token, TokenType};
+
+use std::fs;
+
+/// Lines are wrapped to fit this many columns unless told otherwise.
+pub const DEFAULT_WIDTH: usize = 80;
+
+const INDENT: &str = "  ";
+
+/// Formats the program at `path`, or standard input when `path` is `-`.
+/// A file is rewritten in place, while standard input is formatted to
+/// standard output. With `check`, nothing is written.
+///
+/// Returns whether the formatted program differs from the original.
+pub fn format_file(path: &str, check: bool, width: usize) -> RLoxResult<bool> {
+    let src = interpreter::read_source(path)?;
+    let formatted = format(src.clone(), width)?;
+    let changed = formatted != src;
+
+    if check {
+        if changed {
+            eprintln!("{} is not formatted", path);
+        }
+    } else if path == "-" {
+        print!("{}", formatted);
+    } else if changed {
+        fs::write(path, &formatted)
+            .map_err(|err| RLoxError::io(IoOperation::Write, Some(path), err))?;
+    }
+
+    Ok(changed)
+}
+
+/// Prints `src` in the canonical style: one statement per line, blocks
+/// indented by two spaces with the opening brace on the statement's line,
+/// single spaces around binary operators, and expressions wrapped to
+/// `width` columns where they can be. Comments are kept where they were,
+/// and runs of blank lines shrink to one.
+pub fn format(src: String, width: usize) -> RLoxResult<String> {
+    let cst = Cst::parse(src.clone())?;
+    let mut formatter = Formatter {
+        width,
+        lines: Vec::new(),
+    };
+    formatter.statements(cst.root().children(), 0);
+
+    let mut formatted = formatter.lines.join("\n");
+    if !formatted.is_empty() {
+        formatted.push('\n');
+    }
+
+    // Formatting only ever changes whitespace. Anything else is a bug, and
+    // it is better to refuse than to lose code or comments.
+    if !same_tokens(&src, &formatted) {
+        return Err(RLoxError::source(
+            None,
+            None,
+            "the formatter changed more than whitespace".to_string(),
+        ));
+    }
+    Ok(formatted)
+}
+
+fn same_tokens(before: &str, after: &str) -> bool {
+    let lexemes = |src: &str| -> Option<Vec<String>> {
+        Scanner::new(src.to_string())
+            .lossless()
+            .filter(|token| !matches!(token, Ok(t) if t.token_type == TokenType::Whitespace))
+            .map(|token| token.map(|t| t.lexeme.unwrap_or_default()).ok())
+            .collect()
+    };
+    lexemes(before).is_some() && lexemes(before) == lexemes(after)
+}
+
+struct Formatter {
+    width: usize,
+    lines: Vec<String>,
+}
+
+impl Formatter {
+    /// Lays out the statements and comments of a program or block body,
+    /// `depth` levels deep.
+    fn statements(&mut self, elements: &[SyntaxElement], depth: usize) {
+        let indent = INDENT.repeat(depth);
+        // Line breaks seen since the last statement, brace or comment.
+        let mut newlines = 0;
+        // Whether the source had whitespace since the last element.
+        let mut spaced = false;
+        // Whether the last line ended with something a comment can trail.
+        let mut can_trail = false;
+        // Block comments that start the line of the next statement.
+        let mut prefix = String::new();
+
+        for (index, element) in elements.iter().enumerate() {
+            match element {
+                SyntaxElement::Token(token) => match token.token_type {
+                    TokenType::Whitespace => {
+                        newlines += lexeme(token).matches('\n').count();
+                        spaced = true;
+                        continue;
+                    }
+                    TokenType::Comment if can_trail && newlines == 0 => {
+                        if let Some(line) = self.lines.last_mut() {
+                            if spaced {
+                                line.push(' ');
+                            }
+                            line.push_str(lexeme(token));
+                        }
+                        can_trail = false;
+                    }
+                    TokenType::Comment => match leads_statement(token, &elements[index + 1..]) {
+                        Some(spaced) => {
+                            if prefix.is_empty() {
+                                self.blank_line(newlines);
+                            }
+                            prefix.push_str(lexeme(token));
+                            if spaced {
+                                prefix.push(' ');
+                            }
+                            newlines = 0;
+                        }
+                        None => {
+                            self.blank_line(newlines);
+                            self.lines.push(format!("{}{}", indent, lexeme(token)));
+                            newlines = 0;
+                            can_trail = false;
+                        }
+                    },
+                    // Braces of the enclosing block, which `block` prints.
+                    _ => {
+                        newlines = 0;
+                        can_trail = true;
+                    }
+                },
+                SyntaxElement::Node(node) => {
+                    if prefix.is_empty() {
+                        self.blank_line(newlines);
+                    }
+                    let prefix = std::mem::take(&mut prefix);
+                    self.statement(node, &prefix, depth);
+                    newlines = 0;
+                    can_trail = true;
+                }
+            }
+            spaced = false;
+        }
+    }
+
+    /// Prints a statement `depth` levels deep, after `prefix` on its first
+    /// line.
+    fn statement(&mut self, node: &SyntaxNode, prefix: &str, depth: usize) {
+        match node.kind() {
+            SyntaxKind::Block => self.block(node, prefix, depth),
+            SyntaxKind::Function | SyntaxKind::If | SyntaxKind::While => {
+                self.compound(node, prefix, depth)
+            }
+            _ => {
+                let indent = INDENT.repeat(depth);
+                let column = indent.len() + prefix.chars().count();
+                let text = self.render(node, column, depth, true);
+                self.lines.push(format!("{}{}{}", indent, prefix, text));
+            }
+        }
+    }
+
+    /// Prints a statement that holds other statements, such as an `if`.
+    /// Each nested statement starts on the line of the header before it,
+    /// and an `else` goes on the line of the `}` that ends the block before
+    /// it.
+    fn compound(&mut self, node: &SyntaxNode, prefix: &str, depth: usize) {
+        let indent = INDENT.repeat(depth);
+        let mut line = prefix.to_string();
+        let mut previous: Option<Piece> = None;
+        // Whether the source had whitespace before the current element.
+        let mut spaced = false;
+        // Set just after a nested statement, to whether it was a block.
+        let mut after_statement: Option<bool> = None;
+
+        for element in node.children() {
+            let next = match element {
+                SyntaxElement::Token(token) if token.token_type == TokenType::Whitespace => {
+                    spaced = true;
+                    continue;
+                }
+                SyntaxElement::Node(child) if matches!(child.id(), Some(NodeId::Stmt(_))) => {
+                    if let Some(Piece::LineComment) = previous {
+                        self.lines.push(format!("{}{}", indent, line));
+                        line.clear();
+                    } else if !line.is_empty() && !line.ends_with(' ') {
+                        line.push(' ');
+                    }
+                    self.statement(child, &line, depth);
+                    line.clear();
+                    previous = None;
+                    spaced = false;
+                    after_statement = Some(child.kind() == SyntaxKind::Block);
+                    continue;
+                }
+                SyntaxElement::Token(token) => Piece::of(token),
+                SyntaxElement::Node(_) => Piece::Node,
+            };
+
+            match (after_statement.take(), &next) {
+                // A comment after a nested statement trails its last line.
+                (Some(_), Piece::BlockComment) | (Some(_), Piece::LineComment) => {
+                    if let (Some(last), SyntaxElement::Token(token)) =
+                        (self.lines.last_mut(), element)
+                    {
+                        last.push(' ');
+                        last.push_str(lexeme(token));
+                    }
+                    spaced = false;
+                    continue;
+                }
+                (Some(true), _) => {
+                    if let Some(last) = self.lines.pop() {
+                        line = format!("{} ", last.trim_start());
+                    }
+                }
+                _ => (),
+            }
+
+            if let Some(previous) = &previous {
+                if let Piece::LineComment = previous {
+                    self.lines.push(format!("{}{}", indent, line));
+                    line = INDENT.to_string();
+                } else if needs_space(node.kind(), previous, &next, spaced) {
+                    line.push(' ');
+                }
+            }
+
+            match element {
+                SyntaxElement::Token(token) => line.push_str(lexeme(token)),
+                SyntaxElement::Node(child) => {
+                    let column = indent.len() + line.chars().count();
+                    line.push_str(&self.render(child, column, depth, true));
+                }
+            }
+            previous = Some(next);
+            spaced = false;
+        }
+
+        if !line.trim().is_empty() {
+            self.lines.push(format!("{}{}", indent, line));
+        }
+    }
+
+    /// Prints a block statement, after `prefix` on the line of its `{`.
+    fn block(&mut self, node: &SyntaxNode, prefix: &str, depth: usize) {
+        let indent = INDENT.repeat(depth);
+        let is_empty = node.children().iter().all(|element| match element {
+            SyntaxElement::Token(token) => !matches!(token.token_type, TokenType::Comment),
+            SyntaxElement::Node(_) => false,
+        });
+        if is_empty {
+            self.lines.push(format!("{}{}{{}}", indent, prefix));
+            return;
+        }
+
+        self.lines.push(format!("{}{}{{", indent, prefix));
+        self.statements(node.children(), depth + 1);
+        self.lines.push(format!("{}}}", indent));
+    }
+
+    /// Keeps one blank line where the source had at least one, except at
+    /// the start of a block or the file.
+    fn blank_line(&mut self, newlines: usize) {
+        let at_start = self.lines.last().is_none_or(|line| line.ends_with('{'));
+        if newlines > 1 && !at_start {
+            self.lines.push(String::new());
+        }
+    }
+
+    /// Renders a statement or expression that starts at `column` on a line
+    /// indented `depth` levels. With `wrap`, binary operations, conditionals
+    /// and calls that would run past the width are split over several lines.
+    fn render(&self, node: &SyntaxNode, column: usize, depth: usize, wrap: bool) -> String {
+        let flat = self.join(node, column, depth, false, false);
+        let first_line = flat.lines().next().unwrap_or("");
+        if !wrap || column + first_line.chars().count() <= self.width {
+            return flat;
+        }
+
+        match node.kind() {
+            SyntaxKind::Binary | SyntaxKind::Conditional => {
+                self.join(node, column, depth, true, true)
+            }
+            SyntaxKind::Call => self.call(node, column, depth),
+            // Interpolated strings are left on one line.
+            SyntaxKind::Interpolation => flat,
+            _ => self.join(node, column, depth, true, false),
+        }
+    }
+
+    /// Renders the children of `node` separated by the usual spacing. With
+    /// `break_after_operator`, the line is broken after each operator of the
+    /// node instead: the one of a binary operation, including `,`, or the
+    /// `?` and `:` of a conditional.
+    fn join(
+        &self,
+        node: &SyntaxNode,
+        column: usize,
+        depth: usize,
+        wrap: bool,
+        break_after_operator: bool,
+    ) -> String {
+        let kind = node.kind();
+        let continuation = INDENT.repeat(depth + 1);
+        let mut out = String::new();
+        let mut previous: Option<Piece> = None;
+        // Whether the source had whitespace before the current element.
+        let mut spaced = false;
+
+        for element in node.children() {
+            let next = match element {
+                SyntaxElement::Token(token) if token.token_type == TokenType::Whitespace => {
+                    spaced = true;
+                    continue;
+                }
+                SyntaxElement::Token(token) => Piece::of(token),
+                SyntaxElement::Node(_) => Piece::Node,
+            };
+
+            if let Some(previous) = &previous {
+                if let Piece::LineComment = previous {
+                    out.push('\n');
+                    out.push_str(&continuation);
+                } else if break_after_operator && is_operator(previous) {
+                    out.push('\n');
+                    out.push_str(&continuation);
+                } else if needs_space(kind, previous, &next, spaced) {
+                    out.push(' ');
+                }
+            }
+
+            let text = match element {
+                SyntaxElement::Token(token) => lexeme(token).to_string(),
+                SyntaxElement::Node(child) => {
+                    let (column, depth) = match out.rfind('\n') {
+                        Some(at) => (out[at + 1..].chars().count(), depth + 1),
+                        None => (column + out.chars().count(), depth),
+                    };
+                    let wrap = wrap && kind != SyntaxKind::Interpolation;
+                    self.render(child, column, depth, wrap)
+                }
+            };
+            // Keep `- -x` from running together into a different token.
+            if out.ends_with('-') && text.starts_with('-') {
+                out.push(' ');
+            }
+            out.push_str(&text);
+            previous = Some(next);
+            spaced = false;
+        }
+
+        out
+    }
+
+    /// Renders a call with one argument per line.
+    fn call(&self, node: &SyntaxNode, column: usize, depth: usize) -> String {
+        let indent = INDENT.repeat(depth);
+        let argument_indent = INDENT.repeat(depth + 1);
+        let mut out = String::new();
+        let mut in_arguments = false;
+
+        for element in node.children() {
+            match element {
+                SyntaxElement::Node(child) if !in_arguments => {
+                    out.push_str(&self.render(child, column, depth, true));
+                }
+                SyntaxElement::Node(child) => {
+                    out.push('\n');
+                    out.push_str(&argument_indent);
+                    out.push_str(&self.render(child, argument_indent.len(), depth + 1, true));
+                }
+                SyntaxElement::Token(token) => match &token.token_type {
+                    TokenType::Whitespace => (),
+                    TokenType::LeftParen if !in_arguments => {
+                        out.push('(');
+                        in_arguments = true;
+                    }
+                    TokenType::RightParen => {
+                        out.push('\n');
+                        out.push_str(&indent);
+                        out.push(')');
+                    }
+                    TokenType::Comma => out.push(','),
+                    _ => {
+                        out.push(' ');
+                        out.push_str(lexeme(token));
+                    }
+                },
+            }
+        }
+
+        out
+    }
+}
+
+/// What a child of a node looks like, as far as spacing is concerned.
+enum Piece {
+    Token(String),
+    BlockComment,
+    LineComment,
+    Node,
+}
+
+impl Piece {
+    fn of(token: &Token) -> Piece {
+        match token.token_type {
+            TokenType::Comment if lexeme(token).starts_with("/*") => Piece::BlockComment,
+            TokenType::Comment => Piece::LineComment,
+            _ => Piece::Token(lexeme(token).to_string()),
+        }
+    }
+
+    fn text(&self) -> &str {
+        match self {
+            Piece::Token(text) => text,
+            _ => "",
+        }
+    }
+}
+
+fn is_operator(piece: &Piece) -> bool {
+    matches!(piece, Piece::Token(_))
+}
+
+/// Whether to put a space between two pieces. A block comment keeps to the
+/// token it was written against, so `spaced` says whether the source had
+/// whitespace between them.
+fn needs_space(kind: SyntaxKind, previous: &Piece, next: &Piece, spaced: bool) -> bool {
+    if let ";" | "," | ")" = next.text() {
+        return false;
+    }
+    match (previous, next) {
+        (Piece::BlockComment, _) | (_, Piece::BlockComment) => return spaced,
+        (_, Piece::LineComment) => return true,
+        _ => (),
+    }
+
+    !matches!(
+        (kind, previous.text(), next.text()),
+        (SyntaxKind::Interpolation, _, _)
+            | (SyntaxKind::Unary, _, _)
+            | (SyntaxKind::Increment, _, _)
+            | (SyntaxKind::Call, _, "(")
+            | (SyntaxKind::Function, _, "(")
+            | (_, "(", _)
+    )
+}
+
+/// When the block comment `comment` is followed on the same line by a
+/// statement, returns whether whitespace separates them.
+fn leads_statement(comment: &Token, rest: &[SyntaxElement]) -> Option<bool> {
+    if !lexeme(comment).starts_with("/*") {
+        return None;
+    }
+    match rest {
+        [SyntaxElement::Node(_), ..] => Some(false),
+        [SyntaxElement::Token(space), SyntaxElement::Node(_), ..]
+            if space.token_type == TokenType::Whitespace && !lexeme(space).contains('\n') =>
+        {
+            Some(true)
+        }
+        _ => None,
+    }
+}
+
+fn lexeme(token: &Token) -> &str {
+    token.lexeme.as_deref().unwrap_or("")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fmt(src: &str) -> String {
+        format(src.to_string(), DEFAULT_WIDTH).unwrap()
+    }
+
+    #[test]
+    fn canonical_spacing() {
+        assert_eq!(fmt("var   x=1 ;print x+2;"), "var x = 1;\nprint x + 2;\n");
+        assert_eq!(
+            fmt("print -(-x);print - -x;"),
+            "print -(-x);\nprint - -x;\n"
+        );
+        assert_eq!(fmt("{print x;{}}"), "{\n  print x;\n  {}\n}\n");
+    }
+
+    #[test]
+    fn formatting_is_idempotent() {
+        let sources = [
+            "var   x=1 ;print x+2;",
+            "{print f(1) /* b */;}\n\n\n{ var y = x ? 1 : 2; }",
+            "print 1;   // trailing\n  // own\nvar y=2;",
+            "/* x */print 1;\nprint \"a${ 1+2 }b\";",
+            "print aaaaaaaaaa + bbbbbbbbbb + cccccccccc + dddddddddd + eeeeeeeeee + ffffffffff;",
+            "print 1 + // c\n2;",
+            "fun f(a,b){if(a)return b;else{return a;}}",
+            "if (a) // c\n{ print 1; } else // d\nprint 2;",
+            "while(x>0){x=x-1;}if(a){}else if(b)print 1;",
+        ];
+        for src in &sources {
+            let once = fmt(src);
+            assert_eq!(fmt(&once), once, "formatting {:?} again changed it", src);
+        }
+    }
+
+    #[test]
+    fn comments_stay_where_they_were() {
+        assert_eq!(
+            fmt("print 1;   // trailing\n  // own\nvar y=2;"),
+            "print 1; // trailing\n// own\nvar y = 2;\n"
+        );
+        assert_eq!(
+            fmt("{print f(1) /* b */;}"),
+            "{\n  print f(1) /* b */;\n}\n"
+        );
+        assert_eq!(fmt("/* x */print 1;"), "/* x */print 1;\n");
+        assert_eq!(fmt("/* x */  print 1;"), "/* x */ print 1;\n");
+        assert_eq!(fmt("print 1 + // c\n2;"), "print 1 + // c\n  2;\n");
+    }
+
+    #[test]
+    fn statements_with_bodies() {
+        assert_eq!(
+            fmt("fun  f(a,b){return a+b;}"),
+            "fun f(a, b) {\n  return a + b;\n}\n"
+        );
+        assert_eq!(
+            fmt("if(a){print 1;}else{print 2;}"),
+            "if (a) {\n  print 1;\n} else {\n  print 2;\n}\n"
+        );
+        assert_eq!(
+            fmt("if (a) print 1;\nelse if (b) print 2; else {}"),
+            "if (a) print 1;\nelse if (b) print 2;\nelse {}\n"
+        );
+        assert_eq!(fmt("while(x){x=x-1;}"), "while (x) {\n  x = x - 1;\n}\n");
+        assert_eq!(fmt("fun f() {}"), "fun f() {}\n");
+    }
+
+    #[test]
+    fn blank_lines_shrink_to_one() {
+        assert_eq!(
+            fmt("\n\nprint 1;\n\n\n\nprint 2;\n\n"),
+            "print 1;\n\nprint 2;\n"
+        );
+    }
+
+    #[test]
+    fn long_lines_wrap_to_the_width() {
+        let src = "print aaaaaaaaaa + bbbbbbbbbb + cccccccccc;";
+        assert_eq!(fmt(src), format!("{}\n", src));
+        assert_eq!(
+            format(src.to_string(), 20).unwrap(),
+            "print aaaaaaaaaa +\n  bbbbbbbbbb +\n  cccccccccc;\n"
+        );
+        assert_eq!(
+            format("print f(aaaaaaaaaa, bbbbbbbbbb);".to_string(), 20).unwrap(),
+            "print f(\n  aaaaaaaaaa,\n  bbbbbbbbbb\n);\n"
+        );
+    }
+
+    #[test]
+    fn conditionals_and_commas_wrap_after_their_operators() {
+        let (a, b, c) = ("a".repeat(33), "b".repeat(33), "c".repeat(32));
+        let src = format!("var x = {} ? {} : {};", a, b, c);
+        assert_eq!(src.len(), 113);
+        let expected = format!("var x = {} ?\n  {} :\n  {};\n", a, b, c);
+        assert_eq!(fmt(&src), expected);
+        assert_eq!(fmt(&expected), expected);
+
+        assert_eq!(
+            format("aaaaaaaaaa, bbbbbbbbbb;".to_string(), 20).unwrap(),
+            "aaaaaaaaaa,\n  bbbbbbbbbb;\n"
+        );
+        assert_eq!(
+            format("print a ? bbbbbbbbbb : c ? d : e;".to_string(), 20).unwrap(),
+            "print a ?\n  bbbbbbbbbb :\n  c ? d : e;\n"
+        );
+    }
+
+    #[test]
+    fn syntax_errors_are_not_formatted() {
+        assert!(format("print (1;".to_string(), DEFAULT_WIDTH).is_err());
+    }
+
+    #[test]
+    fn check_reports_without_writing() {
+        let path = std::env::temp_dir().join(format!("rlox-fmt-{}.lox", std::process::id()));
+        let path_str = path.to_str().unwrap();
+        fs::write(&path, "print 1+2;").unwrap();
+
+        assert!(format_file(path_str, true, DEFAULT_WIDTH).unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "print 1+2;");
+
+        assert!(format_file(path_str, false, DEFAULT_WIDTH).unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "print 1 + 2;\n");
+        assert!(!format_file(path_str, true, DEFAULT_WIDTH).unwrap());
+
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,300 @@
+use crate::ast::{
+    AssignNode, Ast, BinaryNode, BlockNode, CallNode, ConditionalNode, ExprId, ExpressionNode,
+    FunctionNode, GroupingNode, IfNode, IncrementNode, InterpolationNode, LiteralNode, NodeId,
+    PrintNode, ReturnNode, StmtId, UnaryNode, VarNode, VariableNode, WhileNode,
+};
+use crate::token::{Span, Token, TokenType};
+use crate::visitor::{self, Visitor};
+
+/// Renders `ast` as a Graphviz `digraph`.
+///
+/// Every node is named after its `ExprId` or `StmtId`, so structurally
+/// identical subtrees stay distinct in the output. Labels show the node
+/// kind, its operator, name or value, and the span it was parsed from. All
+/// top-level statements hang off a single `Program` node.
+pub fn to_dot(ast: &Ast) -> String {
+    let mut printer = GraphvizPrinter { acc: String::new() };
+    printer.acc.push_str("digraph AST {\n");
+    printer
+        .acc
+        .push_str("    node [shape=box, fontname=\"monospace\"];\n");
+    printer.acc.push_str("    program [label=\"Program\"];\n");
+    for &stmt in ast.statements() {
+        printer.acc.push_str(&format!(
+            "    program -> {};\n",
+            node_name(NodeId::Stmt(stmt))
+        ));
+    }
+    printer.visit_program(ast);
+    printer.acc.push('}');
+    printer.acc
+}
+
+/// Escapes `text` for use inside a double-quoted DOT string.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn node_name(id: NodeId) -> String {
+    match id {
+        NodeId::Expr(id) => format!("e{}", id.index()),
+        NodeId::Stmt(id) => format!("s{}", id.index()),
+    }
+}
+
+fn literal_text(value: &TokenType) -> String {
+    match value {
+        TokenType::String(s) => format!("\"{}\"", s),
+        _ => value.to_string(),
+    }
+}
+
+fn name_text(name: &Token) -> String {
+    name.token_type.to_string()
+}
+
+struct GraphvizPrinter {
+    acc: String,
+}
+
+impl GraphvizPrinter {
+    fn push_node(&mut self, id: NodeId, span: Span, kind: &str, text: Option<&str>) {
+        let mut label = kind.to_string();
+        if let Some(text) = text {
+            label.push('\n');
+            label.push_str(text);
+        }
+        label.push('\n');
+        label.push_str(&span.to_string());
+
+        self.acc.push_str(&format!(
+            "    {} [label=\"{}\"];\n",
+            node_name(id),
+            escape(&label)
+        ));
+    }
+
+    fn push_expr(&mut self, ast: &Ast, id: ExprId, kind: &str, text: Option<&str>) {
+        self.push_node(NodeId::Expr(id), ast.span(id), kind, text);
+    }
+
+    fn push_stmt(&mut self, ast: &Ast, id: StmtId, kind: &str, text: Option<&str>) {
+        self.push_node(NodeId::Stmt(id), ast.stmt_span(id), kind, text);
+    }
+
+    fn push_edges(&mut self, from: NodeId, children: &[NodeId]) {
+        for &child in children {
+            self.acc.push_str(&format!(
+                "    {} -> {};\n",
+                node_name(from),
+                node_name(child)
+            ));
+        }
+    }
+
+    fn push_expr_edges(&mut self, from: NodeId, children: &[ExprId]) {
+        let children: Vec<NodeId> = children.iter().map(|&id| NodeId::Expr(id)).collect();
+        self.push_edges(from, &children);
+    }
+}
+
+impl Visitor for GraphvizPrinter {
+    fn visit_block_stmt(&mut self, ast: &Ast, id: StmtId, node: &BlockNode) {
+        self.push_stmt(ast, id, "Block", None);
+        let children: Vec<NodeId> = node.statements().iter().map(|&s| NodeId::Stmt(s)).collect();
+        self.push_edges(NodeId::Stmt(id), &children);
+        visitor::walk_block_stmt(self, ast, node);
+    }
+
+    fn visit_expression_stmt(&mut self, ast: &Ast, id: StmtId, node: &ExpressionNode) {
+        self.push_stmt(ast, id, "Expression", None);
+        self.push_expr_edges(NodeId::Stmt(id), &[node.expression()]);
+        self.visit_expr(ast, node.expression());
+    }
+
+    fn visit_function_stmt(&mut self, ast: &Ast, id: StmtId, node: &FunctionNode) {
+        let params: Vec<String> = node.params().iter().map(name_text).collect();
+        let text = format!("{}({})", name_text(node.name()), params.join(", "));
+        self.push_stmt(ast, id, "Function", Some(&text));
+        self.push_edges(NodeId::Stmt(id), &[NodeId::Stmt(node.body())]);
+        visitor::walk_function_stmt(self, ast, node);
+    }
+
+    fn visit_if_stmt(&mut self, ast: &Ast, id: StmtId, node: &IfNode) {
+        self.push_stmt(ast, id, "If", None);
+        let mut children = vec![
+            NodeId::Expr(node.condition()),
+            NodeId::Stmt(node.then_branch()),
+        ];
+        children.extend(node.else_branch().map(NodeId::Stmt));
+        self.push_edges(NodeId::Stmt(id), &children);
+        visitor::walk_if_stmt(self, ast, node);
+    }
+
+    fn visit_return_stmt(&mut self, ast: &Ast, id: StmtId, node: &ReturnNode) {
+        self.push_stmt(ast, id, "Return", None);
+        let value: Vec<ExprId> = node.value().into_iter().collect();
+        self.push_expr_edges(NodeId::Stmt(id), &value);
+        visitor::walk_return_stmt(self, ast, node);
+    }
+
+    fn visit_while_stmt(&mut self, ast: &Ast, id: StmtId, node: &WhileNode) {
+        self.push_stmt(ast, id, "While", None);
+        let children = [NodeId::Expr(node.condition()), NodeId::Stmt(node.body())];
+        self.push_edges(NodeId::Stmt(id), &children);
+        visitor::walk_while_stmt(self, ast, node);
+    }
+
+    fn visit_print_stmt(&mut self, ast: &Ast, id: StmtId, node: &PrintNode) {
+        self.push_stmt(ast, id, "Print", None);
+        self.push_expr_edges(NodeId::Stmt(id), &[node.expression()]);
+        self.visit_expr(ast, node.expression());
+    }
+
+    fn visit_var_stmt(&mut self, ast: &Ast, id: StmtId, node: &VarNode) {
+        self.push_stmt(ast, id, "Var", Some(&name_text(node.name())));
+        let initializer: Vec<ExprId> = node.initializer().into_iter().collect();
+        self.push_expr_edges(NodeId::Stmt(id), &initializer);
+        visitor::walk_var_stmt(self, ast, node);
+    }
+
+    fn visit_assign(&mut self, ast: &Ast, id: ExprId, node: &AssignNode) {
+        let text = format!("{} {}", name_text(node.name()), node.operator().token_type);
+        self.push_expr(ast, id, "Assign", Some(&text));
+        self.push_expr_edges(NodeId::Expr(id), &[node.value()]);
+        self.visit_expr(ast, node.value());
+    }
+
+    fn visit_binary(&mut self, ast: &Ast, id: ExprId, node: &BinaryNode) {
+        let operator = node.operator().token_type.to_string();
+        self.push_expr(ast, id, "Binary", Some(&operator));
+        self.push_expr_edges(NodeId::Expr(id), &[node.left(), node.right()]);
+        visitor::walk_binary(self, ast, node);
+    }
+
+    fn visit_call(&mut self, ast: &Ast, id: ExprId, node: &CallNode) {
+        self.push_expr(ast, id, "Call", None);
+        let mut children = vec![node.callee()];
+        children.extend(node.arguments());
+        self.push_expr_edges(NodeId::Expr(id), &children);
+        visitor::walk_call(self, ast, node);
+    }
+
+    fn visit_conditional(&mut self, ast: &Ast, id: ExprId, node: &ConditionalNode) {
+        self.push_expr(ast, id, "Conditional", None);
+        let children = [node.condition(), node.then_branch(), node.else_branch()];
+        self.push_expr_edges(NodeId::Expr(id), &children);
+        visitor::walk_conditional(self, ast, node);
+    }
+
+    fn visit_grouping(&mut self, ast: &Ast, id: ExprId, node: &GroupingNode) {
+        self.push_expr(ast, id, "Grouping", None);
+        self.push_expr_edges(NodeId::Expr(id), &[node.child()]);
+        visitor::walk_grouping(self, ast, node);
+    }
+
+    fn visit_increment(&mut self, ast: &Ast, id: ExprId, node: &IncrementNode) {
+        let label = if node.is_prefix() {
+            format!("{}{}", node.operator().token_type, node.name().token_type)
+        } else {
+            format!("{}{}", node.name().token_type, node.operator().token_type)
+        };
+        self.push_expr(ast, id, "Increment", Some(&label));
+    }
+
+    fn visit_interpolation(&mut self, ast: &Ast, id: ExprId, node: &InterpolationNode) {
+        self.push_expr(ast, id, "Interpolation", None);
+        self.push_expr_edges(NodeId::Expr(id), node.parts());
+        visitor::walk_interpolation(self, ast, node);
+    }
+
+    fn visit_literal(&mut self, ast: &Ast, id: ExprId, node: &LiteralNode) {
+        self.push_expr(ast, id, "Literal", Some(&literal_text(node.value())));
+    }
+
+    fn visit_unary(&mut self, ast: &Ast, id: ExprId, node: &UnaryNode) {
+        let operator = node.operator().token_type.to_string();
+        self.push_expr(ast, id, "Unary", Some(&operator));
+        self.push_expr_edges(NodeId::Expr(id), &[node.child()]);
+        visitor::walk_unary(self, ast, node);
+    }
+
+    fn visit_variable(&mut self, ast: &Ast, id: ExprId, node: &VariableNode) {
+        self.push_expr(ast, id, "Variable", Some(&name_text(node.name())));
+    }
+
+    fn visit_error(&mut self, ast: &Ast, id: ExprId) {
+        self.push_expr(ast, id, "Error", None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn dot(src: &str) -> String {
+        let tokens = Scanner::new(src.to_string())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        to_dot(&Parser::new(tokens).parse().unwrap())
+    }
+
+    /// The names of the nodes declared with a label, in order.
+    fn declared(dot: &str) -> Vec<&str> {
+        dot.lines()
+            .filter_map(|line| line.trim().split_once(" [label="))
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    #[test]
+    fn equal_subtrees_get_distinct_nodes() {
+        let dot = dot("print (1 + 1) + (1 + 1);");
+        let names = declared(&dot);
+        let mut unique = names.clone();
+        unique.sort();
+        unique.dedup();
+        assert_eq!(names.len(), unique.len());
+
+        let literals = dot.matches("[label=\"Literal\\n1\\n").count();
+        assert_eq!(literals, 4);
+        // Program, the print, three binaries, two groupings, four literals.
+        assert_eq!(names.len(), 11);
+    }
+
+    #[test]
+    fn quotes_in_labels_are_escaped() {
+        let dot = dot(r#"print "a\"b\\c";"#);
+        assert!(dot.contains(r#"[label="Literal\n\"a\"b\\c\"\n"#));
+        for line in dot.lines().filter(|line| line.contains("[label=")) {
+            let label = line.split_once("[label=").unwrap().1;
+            let unescaped = label
+                .replace("\\\\", "")
+                .replace("\\\"", "")
+                .matches('"')
+                .count();
+            assert_eq!(unescaped, 2, "unbalanced quotes in {}", line);
+        }
+    }
+
+    #[test]
+    fn statements_are_nodes() {
+        let dot = dot("var x = 1;\n{ x = 2; }");
+        assert!(dot.contains("program -> s0;"));
+        assert!(dot.contains("s0 [label=\"Var\\nx\\n1:1-1:11\"];"));
+        assert!(dot.contains("[label=\"Block\\n2:1-2:11\"];"));
+    }
+}
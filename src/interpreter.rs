@@ -1,38 +1,720 @@
-use crate::error::{RLoxError, RLoxResult};
+use crate::ast::{
+    AssignNode, Ast, BinaryNode, BlockNode, CallNode, ConditionalNode, ExprId, ExpressionNode,
+    FunctionNode, GroupingNode, IfNode, IncrementNode, InterpolationNode, LiteralNode, PrintNode,
+    ReturnNode, StmtId, StmtNode, UnaryNode, VarNode, VariableNode, WhileNode,
+};
+use crate::ast_json;
+use crate::cst::{self, Cst};
+use crate::environment::Environment;
+use crate::error::{IoOperation, RLoxError, RLoxResult};
+use crate::graphviz;
+use crate::json::JsonValue;
+use crate::natives;
+use crate::optimizer;
 use crate::parser::Parser;
 use crate::scanner::Scanner;
-use crate::token::Token;
-
-use rustyline::Editor;
+use crate::sexpr;
+use crate::token::{Token, TokenType};
+use crate::token_dump;
+use crate::value::{Function, Value};
+use crate::visitor::ValueVisitor;
 
 use std::fs;
+use std::io::{self, Read};
+use std::mem;
+use std::rc::Rc;
+use std::str::FromStr;
 
-pub fn run_file(path: String) -> RLoxResult<()> {
-    let src = fs::read_to_string(&path)?;
-    run(src)?;
-    Ok(())
+/// An intermediate representation that can be printed instead of running
+/// the program.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Emit {
+    Dot,
+    Sexpr,
+    AstJson,
+    Tokens,
+    TokensJson,
+    Cst,
+    /// The s-expression form of the program after `optimizer::optimize`.
+    OptimizedAst,
 }
 
-pub fn run_prompt() -> RLoxResult<()> {
-    let mut rl = Editor::<()>::new();
+impl FromStr for Emit {
+    type Err = String;
 
-    loop {
-        let line = rl.readline("rlox> ")?;
-        rl.add_history_entry(line.to_string());
-        match run(line) {
-            Err(err @ RLoxError::Source { .. }) => eprintln!("{}", err),
-            err @ Err(_) => return err,
-            Ok(_) => (),
-        };
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(Emit::Dot),
+            "sexpr" => Ok(Emit::Sexpr),
+            "ast-json" => Ok(Emit::AstJson),
+            "tokens" => Ok(Emit::Tokens),
+            "tokens-json" => Ok(Emit::TokensJson),
+            "cst" => Ok(Emit::Cst),
+            "optimized-ast" => Ok(Emit::OptimizedAst),
+            _ => Err(format!("unknown --emit kind: {}", s)),
+        }
+    }
+}
+
+/// Reads the program at `path`, or from standard input when `path` is `-`.
+pub fn read_source(path: &str) -> RLoxResult<String> {
+    if path == "-" {
+        let mut src = String::new();
+        io::stdin()
+            .read_to_string(&mut src)
+            .map_err(|err| RLoxError::io(IoOperation::Read, None::<&str>, err))?;
+        Ok(src)
+    } else {
+        fs::read_to_string(path).map_err(|err| RLoxError::io(IoOperation::Read, Some(path), err))
     }
 }
 
-fn run(src: String) -> RLoxResult<()> {
+/// Runs the program at `path`. `args` are the arguments meant for the
+/// script, read with `arg(i)`, `argc()` and `args()`.
+pub fn run_file(
+    path: String,
+    args: Vec<String>,
+    emit: Option<Emit>,
+    optimize: bool,
+) -> RLoxResult<()> {
+    let src = read_source(&path)?;
+    let mut interpreter = Interpreter::with_args(args);
+    run(&mut interpreter, src, emit, optimize)?;
+    Ok(())
+}
+
+/// Scans and parses the file at `path` without running it.
+pub fn check_file(path: String) -> RLoxResult<()> {
+    let src = read_source(&path)?;
+    let tokens: Result<Vec<Token>, RLoxError> = Scanner::new(src).collect();
+    Parser::new(tokens?).parse()?;
+    Ok(())
+}
+
+/// Scans, parses and runs `src` in `interpreter`, or prints the
+/// representation selected by `emit` instead of running it. With
+/// `optimize`, the AST goes through `optimizer::optimize` first.
+pub fn run(
+    interpreter: &mut Interpreter,
+    src: String,
+    emit: Option<Emit>,
+    optimize: bool,
+) -> RLoxResult<()> {
+    if emit == Some(Emit::Cst) {
+        println!("{}", cst::to_tree(&Cst::parse(src)?));
+        return Ok(());
+    }
+
     let scanner = Scanner::new(src);
+    match emit {
+        Some(Emit::Tokens) => return dump_tokens(scanner),
+        Some(Emit::TokensJson) => return dump_tokens_json(scanner),
+        _ => (),
+    }
+
     let tokens: Result<Vec<Token>, RLoxError> = scanner.collect();
-    // println!("{:?}", tokens.unwrap());
-    let mut parser = Parser::new(tokens.unwrap());
-    let ast = parser.parse();
-    println!("{}", ast.pretty_print());
+    let parser = Parser::new(tokens?);
+    let mut ast = parser.parse()?;
+    if optimize || emit == Some(Emit::OptimizedAst) {
+        optimizer::optimize(&mut ast);
+    }
+    match emit {
+        Some(Emit::Dot) => println!("{}", graphviz::to_dot(&ast)),
+        Some(Emit::Sexpr) | Some(Emit::OptimizedAst) => println!("{}", sexpr::to_sexpr(&ast)),
+        Some(Emit::AstJson) => println!("{}", ast_json::to_json(&ast)),
+        _ => {
+            interpreter.interpret(ast)?;
+        }
+    }
     Ok(())
 }
+
+/// Scans and parses one REPL entry, which may end in a bare expression.
+pub fn parse_line(src: String) -> RLoxResult<Ast> {
+    let tokens: Result<Vec<Token>, RLoxError> = Scanner::new(src).collect();
+    Parser::new(tokens?).allow_bare_expression().parse()
+}
+
+/// Prints tokens as they are scanned, so the output shows how far the
+/// scanner got before any error.
+fn dump_tokens(scanner: Scanner) -> RLoxResult<()> {
+    for token in scanner {
+        println!("{}", token_dump::format_token(&token?));
+    }
+    Ok(())
+}
+
+fn dump_tokens_json(scanner: Scanner) -> RLoxResult<()> {
+    let tokens = scanner
+        .map(|token| token.map(|token| token_dump::token_json(&token)))
+        .collect::<RLoxResult<Vec<JsonValue>>>()?;
+    println!("{}", JsonValue::Array(tokens).to_pretty_string());
+    Ok(())
+}
+
+/// Tree-walking evaluator. Holds the state that outlives a single program,
+/// which is what lets the REPL build on earlier lines.
+pub struct Interpreter {
+    environment: Environment,
+    /// The program being run. Functions declared in it keep it alive.
+    program: Rc<Ast>,
+    /// Command-line arguments for the script.
+    args: Vec<String>,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Interpreter::with_args(Vec::new())
+    }
+
+    pub fn with_args(args: Vec<String>) -> Self {
+        let mut environment = Environment::new();
+        natives::define_natives(&mut environment);
+        Interpreter {
+            environment,
+            program: Rc::new(Ast::new()),
+            args,
+        }
+    }
+
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    /// Global bindings, sorted by name.
+    pub fn globals(&self) -> Vec<(String, Value)> {
+        self.environment.globals()
+    }
+
+    /// Executes the statements of `ast` in order. Returns the value of the
+    /// last statement when it is an expression statement.
+    pub fn interpret(&mut self, ast: Ast) -> RLoxResult<Option<Value>> {
+        let ast = Rc::new(ast);
+        self.program = Rc::clone(&ast);
+        let mut last = None;
+        for &stmt in ast.statements() {
+            let value = self.execute(&ast, stmt)?;
+            last = match ast.get_stmt(stmt) {
+                StmtNode::Expression(_) => Some(value),
+                _ => None,
+            };
+        }
+        Ok(last)
+    }
+
+    fn execute(&mut self, ast: &Ast, id: StmtId) -> RLoxResult<Value> {
+        self.visit_stmt(ast, id)
+    }
+
+    fn evaluate(&mut self, ast: &Ast, id: ExprId) -> RLoxResult<Value> {
+        self.visit_expr(ast, id)
+    }
+
+    fn block(&mut self, ast: &Ast, node: &BlockNode) -> RLoxResult<Value> {
+        self.environment.push_scope();
+        let result = node
+            .statements()
+            .iter()
+            .try_for_each(|&stmt| self.execute(ast, stmt).map(|_| ()));
+        self.environment.pop_scope();
+        result.map(|_| Value::Nil)
+    }
+
+    /// Binds the function declared at `id`, closing over the current scopes.
+    /// It is bound in the innermost of them, so it can call itself.
+    fn function(&mut self, id: StmtId, node: &FunctionNode) -> RLoxResult<Value> {
+        let function = Function {
+            ast: Rc::clone(&self.program),
+            declaration: id,
+            closure: self.environment.capture(),
+        };
+        self.environment.define(
+            &node.name().token_type.to_string(),
+            Value::Function(Rc::new(function)),
+        );
+        Ok(Value::Nil)
+    }
+
+    fn if_statement(&mut self, ast: &Ast, node: &IfNode) -> RLoxResult<Value> {
+        if self.evaluate(ast, node.condition())?.is_truthy() {
+            self.execute(ast, node.then_branch())?;
+        } else if let Some(else_branch) = node.else_branch() {
+            self.execute(ast, else_branch)?;
+        }
+        Ok(Value::Nil)
+    }
+
+    /// Unwinds to the enclosing call, which turns the error back into the
+    /// returned value.
+    fn return_statement(&mut self, ast: &Ast, node: &ReturnNode) -> RLoxResult<Value> {
+        let value = match node.value() {
+            Some(value) => self.evaluate(ast, value)?,
+            None => Value::Nil,
+        };
+        Err(RLoxError::Return { value })
+    }
+
+    fn var(&mut self, ast: &Ast, node: &VarNode) -> RLoxResult<Value> {
+        let value = match node.initializer() {
+            Some(initializer) => self.evaluate(ast, initializer)?,
+            None => Value::Nil,
+        };
+        self.environment
+            .define(&node.name().token_type.to_string(), value);
+        Ok(Value::Nil)
+    }
+
+    fn while_statement(&mut self, ast: &Ast, node: &WhileNode) -> RLoxResult<Value> {
+        while self.evaluate(ast, node.condition())?.is_truthy() {
+            self.execute(ast, node.body())?;
+        }
+        Ok(Value::Nil)
+    }
+
+    /// A compound assignment reads the variable before evaluating the
+    /// right-hand side, as `a = a + b` would.
+    fn assign(&mut self, ast: &Ast, node: &AssignNode) -> RLoxResult<Value> {
+        let name = node.name().token_type.to_string();
+        let operator = node.operator();
+        let value = match operator.token_type.compound_operator() {
+            Some(token_type) => {
+                let current = self.variable(node.name())?;
+                let value = self.evaluate(ast, node.value())?;
+                apply_binary(&token_type, operator, current, value)?
+            }
+            None => self.evaluate(ast, node.value())?,
+        };
+        if self.environment.assign(&name, value.clone()) {
+            Ok(value)
+        } else {
+            Err(undefined_variable(node.name()))
+        }
+    }
+
+    fn binary(&mut self, ast: &Ast, node: &BinaryNode) -> RLoxResult<Value> {
+        let left = self.evaluate(ast, node.left())?;
+        let right = self.evaluate(ast, node.right())?;
+        binary_op(node.operator(), left, right)
+    }
+
+    fn call(&mut self, ast: &Ast, node: &CallNode) -> RLoxResult<Value> {
+        let callee = self.evaluate(ast, node.callee())?;
+        let arguments = node
+            .arguments()
+            .iter()
+            .map(|&argument| self.evaluate(ast, argument))
+            .collect::<RLoxResult<Vec<Value>>>()?;
+        let line = node.paren().line;
+
+        match callee {
+            Value::Native(native) => {
+                check_arity(native.arity, &arguments, line)?;
+                (native.function)(self, &arguments, line)
+            }
+            Value::Function(function) => {
+                check_arity(function.declaration().params().len(), &arguments, line)?;
+                self.call_function(&function, arguments)
+            }
+            _ => Err(RLoxError::runtime(
+                line,
+                "can only call functions".to_string(),
+            )),
+        }
+    }
+
+    /// Runs the body of `function` in a new scope of its closure, holding
+    /// the arguments. A `return` unwinds to here with its value.
+    fn call_function(&mut self, function: &Function, arguments: Vec<Value>) -> RLoxResult<Value> {
+        let declaration = function.declaration();
+        let statements = match function.ast.get_stmt(declaration.body()) {
+            StmtNode::Block(body) => body.statements(),
+            _ => &[],
+        };
+
+        let environment = mem::replace(&mut self.environment, function.closure.capture());
+        let program = mem::replace(&mut self.program, Rc::clone(&function.ast));
+        self.environment.push_scope();
+        for (param, argument) in declaration.params().iter().zip(arguments) {
+            self.environment
+                .define(&param.token_type.to_string(), argument);
+        }
+        let result = statements
+            .iter()
+            .try_for_each(|&stmt| self.execute(&function.ast, stmt).map(|_| ()));
+        self.environment = environment;
+        self.program = program;
+
+        match result {
+            Ok(()) => Ok(Value::Nil),
+            Err(RLoxError::Return { value }) => Ok(value),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn conditional(&mut self, ast: &Ast, node: &ConditionalNode) -> RLoxResult<Value> {
+        if self.evaluate(ast, node.condition())?.is_truthy() {
+            self.evaluate(ast, node.then_branch())
+        } else {
+            self.evaluate(ast, node.else_branch())
+        }
+    }
+
+    fn increment(&mut self, node: &IncrementNode) -> RLoxResult<Value> {
+        let name = node.name().token_type.to_string();
+        let operator = node.operator();
+        let old = match self.environment.get(&name) {
+            Some(Value::Number(n)) => n,
+            Some(_) => {
+                return Err(RLoxError::runtime(
+                    operator.line,
+                    format!("operand of '{}' must be a number", operator.token_type),
+                ))
+            }
+            None => return Err(undefined_variable(node.name())),
+        };
+
+        let new = match operator.token_type {
+            TokenType::PlusPlus => old + 1.0,
+            TokenType::MinusMinus => old - 1.0,
+            _ => return Err(unknown_operator(operator)),
+        };
+        self.environment.assign(&name, Value::Number(new));
+        Ok(Value::Number(if node.is_prefix() { new } else { old }))
+    }
+
+    /// Joins the parts of an interpolated string, formatting each value
+    /// the way `print` would.
+    fn interpolation(&mut self, ast: &Ast, node: &InterpolationNode) -> RLoxResult<Value> {
+        let mut joined = String::new();
+        for &part in node.parts() {
+            joined.push_str(&self.evaluate(ast, part)?.to_string());
+        }
+        Ok(Value::String(joined))
+    }
+
+    fn unary(&mut self, ast: &Ast, node: &UnaryNode) -> RLoxResult<Value> {
+        let operand = self.evaluate(ast, node.child())?;
+        unary_op(node.operator(), operand)
+    }
+
+    fn variable(&self, name: &Token) -> RLoxResult<Value> {
+        self.environment
+            .get(&name.token_type.to_string())
+            .ok_or_else(|| undefined_variable(name))
+    }
+}
+
+/// Applies a binary operator to two evaluated operands. The optimizer
+/// shares it, so folded constants behave exactly as at runtime.
+pub fn binary_op(operator: &Token, left: Value, right: Value) -> RLoxResult<Value> {
+    apply_binary(&operator.token_type, operator, left, right)
+}
+
+/// Applies the operator `token_type`, reporting errors against `operator`.
+/// The two differ for a compound assignment, whose errors name the `+=`
+/// that was written rather than the `+` it applies.
+fn apply_binary(
+    token_type: &TokenType,
+    operator: &Token,
+    left: Value,
+    right: Value,
+) -> RLoxResult<Value> {
+    match (token_type, left, right) {
+        (TokenType::EqualEqual, left, right) => Ok(Value::Boolean(left == right)),
+        (TokenType::BangEqual, left, right) => Ok(Value::Boolean(left != right)),
+        // Both operands have been evaluated for their effects by now.
+        (TokenType::Comma, _, right) => Ok(right),
+        (TokenType::Plus, Value::Number(a), Value::Number(b)) => Ok(Value::Number(a + b)),
+        (TokenType::Plus, Value::String(a), Value::String(b)) => Ok(Value::String(a + &b)),
+        (TokenType::Plus, _, _) => Err(RLoxError::runtime(
+            operator.line,
+            format!(
+                "operands of '{}' must be two numbers or two strings",
+                operator.token_type
+            ),
+        )),
+        (token_type, Value::Number(a), Value::Number(b)) => match token_type {
+            TokenType::Minus => Ok(Value::Number(a - b)),
+            TokenType::Star => Ok(Value::Number(a * b)),
+            TokenType::Slash => Ok(Value::Number(a / b)),
+            TokenType::Percent => Ok(Value::Number(a % b)),
+            TokenType::StarStar => Ok(Value::Number(a.powf(b))),
+            TokenType::Greater => Ok(Value::Boolean(a > b)),
+            TokenType::GreaterEqual => Ok(Value::Boolean(a >= b)),
+            TokenType::Less => Ok(Value::Boolean(a < b)),
+            TokenType::LessEqual => Ok(Value::Boolean(a <= b)),
+            _ => Err(unknown_operator(operator)),
+        },
+        _ => Err(RLoxError::runtime(
+            operator.line,
+            format!("operands of '{}' must be numbers", operator.token_type),
+        )),
+    }
+}
+
+/// Applies a unary operator to an evaluated operand.
+pub fn unary_op(operator: &Token, operand: Value) -> RLoxResult<Value> {
+    match (&operator.token_type, operand) {
+        (TokenType::Bang, operand) => Ok(Value::Boolean(!operand.is_truthy())),
+        (TokenType::Minus, Value::Number(n)) => Ok(Value::Number(-n)),
+        (TokenType::Minus, _) => Err(RLoxError::runtime(
+            operator.line,
+            format!("operand of '{}' must be a number", operator.token_type),
+        )),
+        _ => Err(unknown_operator(operator)),
+    }
+}
+
+fn check_arity(arity: usize, arguments: &[Value], line: Option<usize>) -> RLoxResult<()> {
+    if arguments.len() == arity {
+        return Ok(());
+    }
+    Err(RLoxError::runtime(
+        line,
+        format!("expected {} arguments but got {}", arity, arguments.len()),
+    ))
+}
+
+fn undefined_variable(name: &Token) -> RLoxError {
+    RLoxError::runtime(
+        name.line,
+        format!("undefined variable '{}'", name.token_type),
+    )
+}
+
+fn unknown_operator(operator: &Token) -> RLoxError {
+    RLoxError::runtime(
+        operator.line,
+        format!("unknown operator '{}'", operator.token_type),
+    )
+}
+
+pub fn literal_value(value: &TokenType) -> Value {
+    match value {
+        TokenType::Number(n) => Value::Number(*n),
+        TokenType::String(s) => Value::String(s.clone()),
+        TokenType::True => Value::Boolean(true),
+        TokenType::False => Value::Boolean(false),
+        _ => Value::Nil,
+    }
+}
+
+impl ValueVisitor for Interpreter {
+    type Output = RLoxResult<Value>;
+
+    fn visit_block_stmt(&mut self, ast: &Ast, _id: StmtId, node: &BlockNode) -> RLoxResult<Value> {
+        self.block(ast, node)
+    }
+
+    fn visit_expression_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        node: &ExpressionNode,
+    ) -> RLoxResult<Value> {
+        self.evaluate(ast, node.expression())
+    }
+
+    fn visit_function_stmt(
+        &mut self,
+        _ast: &Ast,
+        id: StmtId,
+        node: &FunctionNode,
+    ) -> RLoxResult<Value> {
+        self.function(id, node)
+    }
+
+    fn visit_if_stmt(&mut self, ast: &Ast, _id: StmtId, node: &IfNode) -> RLoxResult<Value> {
+        self.if_statement(ast, node)
+    }
+
+    fn visit_return_stmt(
+        &mut self,
+        ast: &Ast,
+        _id: StmtId,
+        node: &ReturnNode,
+    ) -> RLoxResult<Value> {
+        self.return_statement(ast, node)
+    }
+
+    fn visit_while_stmt(&mut self, ast: &Ast, _id: StmtId, node: &WhileNode) -> RLoxResult<Value> {
+        self.while_statement(ast, node)
+    }
+
+    fn visit_print_stmt(&mut self, ast: &Ast, _id: StmtId, node: &PrintNode) -> RLoxResult<Value> {
+        self.evaluate(ast, node.expression()).map(|value| {
+            println!("{}", value);
+            Value::Nil
+        })
+    }
+
+    fn visit_var_stmt(&mut self, ast: &Ast, _id: StmtId, node: &VarNode) -> RLoxResult<Value> {
+        self.var(ast, node)
+    }
+
+    fn visit_assign(&mut self, ast: &Ast, _id: ExprId, node: &AssignNode) -> RLoxResult<Value> {
+        self.assign(ast, node)
+    }
+
+    fn visit_binary(&mut self, ast: &Ast, _id: ExprId, node: &BinaryNode) -> RLoxResult<Value> {
+        self.binary(ast, node)
+    }
+
+    fn visit_call(&mut self, ast: &Ast, _id: ExprId, node: &CallNode) -> RLoxResult<Value> {
+        self.call(ast, node)
+    }
+
+    fn visit_conditional(
+        &mut self,
+        ast: &Ast,
+        _id: ExprId,
+        node: &ConditionalNode,
+    ) -> RLoxResult<Value> {
+        self.conditional(ast, node)
+    }
+
+    fn visit_grouping(&mut self, ast: &Ast, _id: ExprId, node: &GroupingNode) -> RLoxResult<Value> {
+        self.evaluate(ast, node.child())
+    }
+
+    fn visit_increment(
+        &mut self,
+        _ast: &Ast,
+        _id: ExprId,
+        node: &IncrementNode,
+    ) -> RLoxResult<Value> {
+        self.increment(node)
+    }
+
+    fn visit_interpolation(
+        &mut self,
+        ast: &Ast,
+        _id: ExprId,
+        node: &InterpolationNode,
+    ) -> RLoxResult<Value> {
+        self.interpolation(ast, node)
+    }
+
+    fn visit_literal(&mut self, _ast: &Ast, _id: ExprId, node: &LiteralNode) -> RLoxResult<Value> {
+        Ok(literal_value(node.value()))
+    }
+
+    fn visit_unary(&mut self, ast: &Ast, _id: ExprId, node: &UnaryNode) -> RLoxResult<Value> {
+        self.unary(ast, node)
+    }
+
+    fn visit_variable(
+        &mut self,
+        _ast: &Ast,
+        _id: ExprId,
+        node: &VariableNode,
+    ) -> RLoxResult<Value> {
+        self.variable(node.name())
+    }
+
+    fn visit_error(&mut self, ast: &Ast, id: ExprId) -> RLoxResult<Value> {
+        Err(RLoxError::runtime(
+            Some(ast.span(id).start.line),
+            "cannot evaluate an invalid expression".to_string(),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The line and message of the runtime error raised by `src`.
+    fn runtime_error(src: &str) -> (Option<usize>, String) {
+        let ast = parse_line(src.to_string()).unwrap();
+        match Interpreter::new().interpret(ast) {
+            Err(RLoxError::Runtime { line, message }) => (line, message),
+            other => panic!("expected a runtime error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn compound_assignment_errors_name_the_operator() {
+        assert_eq!(
+            runtime_error("var x = \"a\";\nx += 1;"),
+            (
+                Some(2),
+                "operands of '+=' must be two numbers or two strings".to_string()
+            )
+        );
+        assert_eq!(
+            runtime_error("var x = nil;\nx *= 2;"),
+            (Some(2), "operands of '*=' must be numbers".to_string())
+        );
+        assert_eq!(
+            runtime_error("nil - 1;"),
+            (Some(1), "operands of '-' must be numbers".to_string())
+        );
+    }
+
+    #[test]
+    fn compound_assignments_read_the_variable_first() {
+        let ast = parse_line("var x = 1;\nx += (x = 10);\nx".to_string()).unwrap();
+        let value = Interpreter::new().interpret(ast).unwrap();
+        assert_eq!(value, Some(Value::Number(11.0)));
+        assert_eq!(
+            runtime_error("y += 1;"),
+            (Some(1), "undefined variable 'y'".to_string())
+        );
+    }
+
+    /// The value of the last expression statement in `src`.
+    fn eval(src: &str) -> Value {
+        let ast = parse_line(src.to_string()).unwrap();
+        Interpreter::new().interpret(ast).unwrap().unwrap()
+    }
+
+    #[test]
+    fn functions_return_values() {
+        assert_eq!(
+            eval("fun f(a, b) { return a + b; }\nf(1, 2)"),
+            Value::Number(3.0)
+        );
+        assert_eq!(eval("fun f() { return; }\nf()"), Value::Nil);
+        assert_eq!(eval("fun f() {}\nf()"), Value::Nil);
+        let src = "fun fib(n) { if (n < 2) return n; return fib(n - 1) + fib(n - 2); }\nfib(15)";
+        assert_eq!(eval(src), Value::Number(610.0));
+    }
+
+    #[test]
+    fn return_unwinds_loops_and_blocks() {
+        let src = "fun f() { var i = 0; while (true) { i++; if (i == 3) { return i; } } }\nf()";
+        assert_eq!(eval(src), Value::Number(3.0));
+    }
+
+    #[test]
+    fn closures_share_their_variables() {
+        let src = "fun counter() { var n = 0; fun inc() { n++; return n; } return inc; }
+var c = counter();
+c();
+var d = counter();
+c() * 10 + d()";
+        assert_eq!(eval(src), Value::Number(21.0));
+    }
+
+    #[test]
+    fn if_and_while_follow_truthiness() {
+        assert_eq!(
+            eval("var x = 0;\nif (nil) x = 1; else x = 2;\nx"),
+            Value::Number(2.0)
+        );
+        assert_eq!(eval("var x = 0;\nif (0) x = 1;\nx"), Value::Number(1.0));
+        assert_eq!(
+            eval("var x = 5;\nwhile (x > 1) x -= 2;\nx"),
+            Value::Number(1.0)
+        );
+    }
+
+    #[test]
+    fn calls_check_their_arity() {
+        assert_eq!(
+            runtime_error("fun f(a) {}\nf(1,\n2);"),
+            (Some(3), "expected 1 arguments but got 2".to_string())
+        );
+    }
+}
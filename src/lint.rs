@@ -0,0 +1,695 @@
+use crate::ast::{
+    ASTNode, Ast, BinaryNode, BlockNode, ExprId, FunctionNode, IfNode, ReturnNode, StmtId,
+    StmtNode, VarNode, VariableNode, WhileNode,
+};
+use crate::error::{self, RLoxError, RLoxResult};
+use crate::interpreter;
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::token::{Token, TokenType};
+use crate::visitor::{self, Visitor};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Name of the per-project file that changes lint severities. It is looked
+/// up in the script's directory and then in each parent directory.
+pub const CONFIG_FILE: &str = ".rloxlint";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Allow,
+    Warning,
+    Error,
+}
+
+impl std::str::FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow" => Ok(Severity::Allow),
+            "warn" => Ok(Severity::Warning),
+            "deny" => Ok(Severity::Error),
+            _ => Err(format!(
+                "severity must be allow, warn or deny, found '{}'",
+                s
+            )),
+        }
+    }
+}
+
+pub struct Rule {
+    pub code: &'static str,
+    pub severity: Severity,
+}
+
+/// Every lint, with its default severity.
+///
+/// There is no resolver, so scopes are tracked by the linter itself.
+/// `while (true)` is not a constant condition, since it is how an endless
+/// loop is written.
+pub const RULES: &[Rule] = &[
+    Rule {
+        code: "unused-variable",
+        severity: Severity::Warning,
+    },
+    Rule {
+        code: "unused-parameter",
+        severity: Severity::Warning,
+    },
+    Rule {
+        code: "shadowed-variable",
+        severity: Severity::Warning,
+    },
+    Rule {
+        code: "self-comparison",
+        severity: Severity::Error,
+    },
+    Rule {
+        code: "empty-block",
+        severity: Severity::Warning,
+    },
+    Rule {
+        code: "unreachable-code",
+        severity: Severity::Warning,
+    },
+    Rule {
+        code: "assignment-in-condition",
+        severity: Severity::Warning,
+    },
+    Rule {
+        code: "constant-condition",
+        severity: Severity::Warning,
+    },
+];
+
+/// Severities for a project, starting from each rule's default.
+///
+/// The config file holds one `code = allow|warn|deny` pair per line. Blank
+/// lines and lines starting with `#` are ignored:
+///
+/// ```text
+/// unused-variable = deny
+/// empty-block = allow
+/// ```
+#[derive(Debug, Clone)]
+pub struct LintConfig {
+    severities: HashMap<&'static str, Severity>,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        LintConfig {
+            severities: RULES
+                .iter()
+                .map(|rule| (rule.code, rule.severity))
+                .collect(),
+        }
+    }
+}
+
+impl LintConfig {
+    /// Reads the nearest `CONFIG_FILE` at or above `dir`. Unlike the REPL
+    /// config, a bad line is an error, since it would silently change what
+    /// gets reported.
+    pub fn load(dir: &Path) -> RLoxResult<Self> {
+        let mut config = LintConfig::default();
+        let path = match dir
+            .ancestors()
+            .map(|d| d.join(CONFIG_FILE))
+            .find(|p| p.is_file())
+        {
+            Some(path) => path,
+            None => return Ok(config),
+        };
+        let src = fs::read_to_string(&path)
+            .map_err(|err| RLoxError::io(error::IoOperation::Read, Some(path.as_path()), err))?;
+
+        for (index, line) in src.lines().enumerate() {
+            if let Err(message) = config.set_line(line) {
+                let context = format!("in {}", path.display());
+                return Err(RLoxError::source(Some(index + 1), Some(context), message));
+            }
+        }
+        Ok(config)
+    }
+
+    fn set_line(&mut self, line: &str) -> Result<(), String> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(());
+        }
+
+        let (code, value) = match line.find('=') {
+            Some(at) => (line[..at].trim(), line[at + 1..].trim()),
+            None => return Err(format!("expected 'code = severity', found '{}'", line)),
+        };
+        let rule = RULES
+            .iter()
+            .find(|rule| rule.code == code)
+            .ok_or_else(|| format!("unknown lint '{}'", code))?;
+        self.severities.insert(rule.code, value.parse()?);
+        Ok(())
+    }
+
+    pub fn severity(&self, code: &str) -> Severity {
+        self.severities
+            .get(code)
+            .copied()
+            .unwrap_or(Severity::Allow)
+    }
+}
+
+/// A problem found by a lint.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub code: &'static str,
+    pub severity: Severity,
+    pub line: Option<usize>,
+    pub context: String,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self.severity {
+            Severity::Error => "Error",
+            _ => "Warning",
+        };
+        let message = format!("{} [{}]", self.message, self.code);
+        error::write_diagnostic(f, self.line, label, Some(&self.context), &message)
+    }
+}
+
+/// Lints the script at `path`, or standard input when `path` is `-`, and
+/// prints what it finds to stderr. Returns the number of errors.
+pub fn lint_file(path: &str) -> RLoxResult<usize> {
+    let src = interpreter::read_source(path)?;
+    let tokens: Result<Vec<Token>, RLoxError> = Scanner::new(src).collect();
+    let ast = Parser::new(tokens?).parse()?;
+
+    let dir = match Path::new(path).parent() {
+        Some(dir) if path != "-" && !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => Path::new(".").to_path_buf(),
+    };
+    let config = LintConfig::load(&dir)?;
+
+    let diagnostics = lint(&ast, &config);
+    for diagnostic in &diagnostics {
+        eprintln!("{}", diagnostic);
+    }
+    Ok(diagnostics
+        .iter()
+        .filter(|diagnostic| diagnostic.severity == Severity::Error)
+        .count())
+}
+
+/// Runs every lint that `config` does not allow over `ast`, returning the
+/// diagnostics in source order.
+pub fn lint(ast: &Ast, config: &LintConfig) -> Vec<Diagnostic> {
+    let mut linter = Linter {
+        config,
+        globals: HashMap::new(),
+        scopes: Vec::new(),
+        diagnostics: Vec::new(),
+    };
+    linter.visit_program(ast);
+
+    let mut diagnostics = linter.diagnostics;
+    diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+    diagnostics
+}
+
+struct Local {
+    name: Token,
+    used: bool,
+    is_parameter: bool,
+}
+
+struct Linter<'a> {
+    config: &'a LintConfig,
+    /// Line of each global's declaration.
+    globals: HashMap<String, Option<usize>>,
+    scopes: Vec<Vec<Local>>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Linter<'_> {
+    fn report(
+        &mut self,
+        code: &'static str,
+        line: Option<usize>,
+        context: String,
+        message: String,
+    ) {
+        let severity = self.config.severity(code);
+        if severity != Severity::Allow {
+            self.diagnostics.push(Diagnostic {
+                code,
+                severity,
+                line,
+                context,
+                message,
+            });
+        }
+    }
+
+    /// The line an enclosing variable called `name` was declared on, if
+    /// there is one.
+    fn outer_declaration(&self, name: &str) -> Option<Option<usize>> {
+        let scopes = &self.scopes[..self.scopes.len() - 1];
+        scopes
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev())
+            .find(|local| lexeme(&local.name) == name)
+            .map(|local| local.name.line)
+            .or_else(|| self.globals.get(name).copied())
+    }
+
+    fn mark_used(&mut self, name: &str) {
+        let local = self
+            .scopes
+            .iter_mut()
+            .rev()
+            .flat_map(|scope| scope.iter_mut().rev())
+            .find(|local| lexeme(&local.name) == name);
+        if let Some(local) = local {
+            local.used = true;
+        }
+    }
+
+    /// Declares a variable, function or parameter in the innermost scope,
+    /// or as a global outside of any block.
+    fn declare(&mut self, name: &Token, is_parameter: bool) {
+        if self.scopes.is_empty() {
+            self.globals.insert(lexeme(name).to_string(), name.line);
+            return;
+        }
+
+        if let Some(line) = self.outer_declaration(lexeme(name)) {
+            let message = format!(
+                "'{}' shadows a variable declared on line {}",
+                lexeme(name),
+                line.unwrap_or(0)
+            );
+            self.report("shadowed-variable", name.line, at(lexeme(name)), message);
+        }
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push(Local {
+                name: name.clone(),
+                used: false,
+                is_parameter,
+            });
+        }
+    }
+
+    fn end_scope(&mut self) {
+        for local in self.scopes.pop().unwrap_or_default() {
+            let name = lexeme(&local.name).to_string();
+            if local.used {
+                continue;
+            }
+            if !local.is_parameter {
+                let message = format!("variable '{}' is never used", name);
+                self.report("unused-variable", local.name.line, at(&name), message);
+            } else {
+                let message = format!("parameter '{}' is never used", name);
+                self.report("unused-parameter", local.name.line, at(&name), message);
+            }
+        }
+    }
+
+    /// Reports an assignment used directly as the condition of an `if` or
+    /// `while`, which is usually a mistyped `==`. Parenthesizing it again,
+    /// as in `while ((x = next()))`, says it is meant.
+    fn assignment_in_condition(&mut self, ast: &Ast, condition: ExprId) {
+        if let ASTNode::Assign(node) = ast.get(condition) {
+            let operator = node.operator();
+            let message = "assignment used as a condition".to_string();
+            self.report(
+                "assignment-in-condition",
+                operator.line,
+                at(lexeme(operator)),
+                message,
+            );
+        }
+    }
+}
+
+impl Visitor for Linter<'_> {
+    fn visit_block_stmt(&mut self, ast: &Ast, id: StmtId, node: &BlockNode) {
+        if node.statements().is_empty() {
+            let line = Some(ast.stmt_span(id).start.line);
+            self.report("empty-block", line, at("{"), "empty block".to_string());
+        }
+
+        self.scopes.push(Vec::new());
+        visitor::walk_block_stmt(self, ast, node);
+        self.end_scope();
+    }
+
+    /// The parameters and the body share one scope, as they do at runtime,
+    /// so an empty body is not reported as an empty block.
+    fn visit_function_stmt(&mut self, ast: &Ast, _id: StmtId, node: &FunctionNode) {
+        self.declare(node.name(), false);
+
+        self.scopes.push(Vec::new());
+        for param in node.params() {
+            self.declare(param, true);
+        }
+        if let StmtNode::Block(body) = ast.get_stmt(node.body()) {
+            visitor::walk_block_stmt(self, ast, body);
+        }
+        self.end_scope();
+    }
+
+    fn visit_if_stmt(&mut self, ast: &Ast, _id: StmtId, node: &IfNode) {
+        self.assignment_in_condition(ast, node.condition());
+        visitor::walk_if_stmt(self, ast, node);
+    }
+
+    fn visit_return_stmt(&mut self, ast: &Ast, id: StmtId, node: &ReturnNode) {
+        visitor::walk_return_stmt(self, ast, node);
+
+        // Only the first statement after the first `return` of a block is
+        // reported; the rest of the block is unreachable for the same reason.
+        let statements = match ast.stmt_parent(id).map(|parent| ast.get_stmt(parent)) {
+            Some(StmtNode::Block(block)) => block.statements(),
+            _ => return,
+        };
+        let returns = statements
+            .iter()
+            .position(|&stmt| matches!(ast.get_stmt(stmt), StmtNode::Return(_)));
+        if returns.map(|at| statements[at]) != Some(id) {
+            return;
+        }
+        if let Some(&next) = returns.and_then(|at| statements.get(at + 1)) {
+            let line = Some(ast.stmt_span(next).start.line);
+            let message = "unreachable code".to_string();
+            self.report(
+                "unreachable-code",
+                line,
+                "after 'return'".to_string(),
+                message,
+            );
+        }
+    }
+
+    fn visit_while_stmt(&mut self, ast: &Ast, id: StmtId, node: &WhileNode) {
+        self.assignment_in_condition(ast, node.condition());
+
+        let condition = node.condition();
+        let is_true = matches!(
+            ast.get(condition),
+            ASTNode::Literal(literal) if *literal.value() == TokenType::True
+        );
+        if is_constant(ast, condition) && !is_true {
+            let line = Some(ast.stmt_span(id).start.line);
+            let message = "loop condition is always the same".to_string();
+            self.report("constant-condition", line, at("while"), message);
+        }
+
+        visitor::walk_while_stmt(self, ast, node);
+    }
+
+    fn visit_var_stmt(&mut self, ast: &Ast, _id: StmtId, node: &VarNode) {
+        visitor::walk_var_stmt(self, ast, node);
+        self.declare(node.name(), false);
+    }
+
+    fn visit_binary(&mut self, ast: &Ast, _id: ExprId, node: &BinaryNode) {
+        visitor::walk_binary(self, ast, node);
+
+        let operator = node.operator();
+        let is_comparison = matches!(
+            operator.token_type,
+            TokenType::EqualEqual
+                | TokenType::BangEqual
+                | TokenType::Less
+                | TokenType::LessEqual
+                | TokenType::Greater
+                | TokenType::GreaterEqual
+        );
+        if !is_comparison {
+            return;
+        }
+        if let (Some(left), Some(right)) = (
+            variable_name(ast, node.left()),
+            variable_name(ast, node.right()),
+        ) {
+            if left == right {
+                let message = format!("'{}' is compared with itself", left);
+                self.report(
+                    "self-comparison",
+                    operator.line,
+                    at(lexeme(operator)),
+                    message,
+                );
+            }
+        }
+    }
+
+    fn visit_variable(&mut self, _ast: &Ast, _id: ExprId, node: &VariableNode) {
+        self.mark_used(lexeme(node.name()));
+    }
+}
+
+/// Whether `id` always has the same value, being made only of literals.
+fn is_constant(ast: &Ast, id: ExprId) -> bool {
+    match ast.get(id) {
+        ASTNode::Literal(_) => true,
+        ASTNode::Grouping(node) => is_constant(ast, node.child()),
+        ASTNode::Unary(node) => is_constant(ast, node.child()),
+        ASTNode::Binary(node) => is_constant(ast, node.left()) && is_constant(ast, node.right()),
+        _ => false,
+    }
+}
+
+/// The name of the variable `id` reads, looking through parentheses.
+fn variable_name(ast: &Ast, id: ExprId) -> Option<&str> {
+    match ast.get(id) {
+        ASTNode::Grouping(node) => variable_name(ast, node.child()),
+        ASTNode::Variable(node) => Some(lexeme(node.name())),
+        _ => None,
+    }
+}
+
+fn at(text: &str) -> String {
+    format!("at '{}'", text)
+}
+
+fn lexeme(token: &Token) -> &str {
+    token.lexeme.as_deref().unwrap_or("")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(src: &str, config: &LintConfig) -> Vec<&'static str> {
+        let tokens: RLoxResult<Vec<Token>> = Scanner::new(src.to_string()).collect();
+        let ast = Parser::new(tokens.unwrap()).parse().unwrap();
+        lint(&ast, config)
+            .iter()
+            .map(|diagnostic| diagnostic.code)
+            .collect()
+    }
+
+    fn default_codes(src: &str) -> Vec<&'static str> {
+        codes(src, &LintConfig::default())
+    }
+
+    #[test]
+    fn unused_variable() {
+        assert_eq!(default_codes("{ var x = 1; }"), vec!["unused-variable"]);
+        assert!(default_codes("{ var x = 1; print x; }").is_empty());
+        // Globals may be used by code that isn't written yet.
+        assert!(default_codes("var x = 1;").is_empty());
+    }
+
+    #[test]
+    fn writes_are_not_uses() {
+        assert_eq!(
+            default_codes("{ var y = 0; y += 1; }"),
+            vec!["unused-variable"]
+        );
+        assert_eq!(
+            default_codes("{ var y = 0; y = 1; }"),
+            vec!["unused-variable"]
+        );
+        assert_eq!(
+            default_codes("{ var x = 1; x++; }"),
+            vec!["unused-variable"]
+        );
+        assert_eq!(
+            default_codes("{ var x = 1; --x; }"),
+            vec!["unused-variable"]
+        );
+        assert!(default_codes("{ var x = 1; x++; print x; }").is_empty());
+        assert!(default_codes("{ var y = 0; y += 1; print y; }").is_empty());
+        assert!(default_codes("{ var y = 0; var z = 0; z += y; print z; }").is_empty());
+    }
+
+    #[test]
+    fn shadowed_variable() {
+        assert_eq!(
+            default_codes("var x = 1; { var x = 2; print x; }"),
+            vec!["shadowed-variable"]
+        );
+        assert_eq!(
+            default_codes("{ var x = 1; { var x = 2; print x; } print x; }"),
+            vec!["shadowed-variable"]
+        );
+        assert!(default_codes("{ var x = 1; print x; } { var x = 2; print x; }").is_empty());
+    }
+
+    #[test]
+    fn self_comparison() {
+        assert_eq!(
+            default_codes("var x; print x == x;"),
+            vec!["self-comparison"]
+        );
+        assert_eq!(
+            default_codes("var x; print (x) < x;"),
+            vec!["self-comparison"]
+        );
+        assert!(default_codes("var x; var y; print x == y;").is_empty());
+        assert!(default_codes("var x; print x + x;").is_empty());
+    }
+
+    #[test]
+    fn empty_block() {
+        assert_eq!(default_codes("{}"), vec!["empty-block"]);
+        assert!(default_codes("{ print 1; }").is_empty());
+    }
+
+    #[test]
+    fn diagnostics_are_in_source_order() {
+        let src = "var x;\n{ var a = 1; }\nprint x == x;\n{}";
+        assert_eq!(
+            default_codes(src),
+            vec!["unused-variable", "self-comparison", "empty-block"]
+        );
+    }
+
+    #[test]
+    fn config_changes_severities() {
+        let mut config = LintConfig::default();
+        assert_eq!(config.severity("self-comparison"), Severity::Error);
+        assert_eq!(config.severity("empty-block"), Severity::Warning);
+
+        config.set_line("  # a comment").unwrap();
+        config.set_line("").unwrap();
+        config.set_line("empty-block = allow").unwrap();
+        config.set_line("unused-variable=deny").unwrap();
+        assert_eq!(config.severity("empty-block"), Severity::Allow);
+        assert_eq!(config.severity("unused-variable"), Severity::Error);
+
+        assert!(codes("{}", &config).is_empty());
+    }
+
+    #[test]
+    fn bad_config_lines_are_errors() {
+        let mut config = LintConfig::default();
+        assert!(config.set_line("empty-block").is_err());
+        assert!(config.set_line("no-such-lint = warn").is_err());
+        assert!(config.set_line("empty-block = loud").is_err());
+    }
+
+    #[test]
+    fn config_is_found_in_a_parent_directory() {
+        let root = std::env::temp_dir().join(format!("rlox-lint-{}", std::process::id()));
+        let nested = root.join("src");
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(root.join(CONFIG_FILE), "empty-block = deny\n").unwrap();
+
+        let config = LintConfig::load(&nested).unwrap();
+        assert_eq!(config.severity("empty-block"), Severity::Error);
+
+        fs::write(root.join(CONFIG_FILE), "empty-block = deny\nbogus\n").unwrap();
+        assert!(matches!(
+            LintConfig::load(&nested),
+            Err(RLoxError::Source { line: Some(2), .. })
+        ));
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn unused_parameter() {
+        assert_eq!(
+            default_codes("fun f(a, b) { print a; }"),
+            vec!["unused-parameter"]
+        );
+        assert!(default_codes("fun f(a) { a = 1; print a; }").is_empty());
+    }
+
+    #[test]
+    fn unreachable_code() {
+        assert_eq!(
+            default_codes("fun f() { return 1; print 2; print 3; }"),
+            vec!["unreachable-code"]
+        );
+        assert_eq!(
+            default_codes("fun f(a) { while (a) { return; a--; } }"),
+            vec!["unreachable-code"]
+        );
+        assert_eq!(
+            default_codes("fun f() { return 1; return 2; print 3; }"),
+            vec!["unreachable-code"]
+        );
+        assert!(default_codes("fun f(a) { if (a) return 1; return 2; }").is_empty());
+    }
+
+    #[test]
+    fn assignment_in_condition() {
+        assert_eq!(
+            default_codes("var x; var y; if (x = y) print x;"),
+            vec!["assignment-in-condition"]
+        );
+        assert_eq!(
+            default_codes("var x; while (x += 1) print x;"),
+            vec!["assignment-in-condition"]
+        );
+        assert!(default_codes("var x; var y; if ((x = y)) print x;").is_empty());
+        assert!(default_codes("var x; var y; if (x == y) print x;").is_empty());
+    }
+
+    #[test]
+    fn constant_condition() {
+        for src in &[
+            "while (false) {}",
+            "while (1) {}",
+            "while (!nil) {}",
+            "while (1 < 2) {}",
+        ] {
+            let codes: Vec<_> = default_codes(src)
+                .into_iter()
+                .filter(|&code| code != "empty-block")
+                .collect();
+            assert_eq!(codes, vec!["constant-condition"], "{}", src);
+        }
+        assert!(default_codes("while (true) print 1;").is_empty());
+        assert!(default_codes("var x; while (x) print 1;").is_empty());
+    }
+
+    #[test]
+    fn functions_have_their_own_scope() {
+        assert!(default_codes("fun f(a) { print a; }").is_empty());
+        assert!(default_codes("fun f() {}").is_empty());
+        assert!(default_codes("{ var x; fun f() { print x; } f(); }").is_empty());
+        assert_eq!(
+            default_codes("fun f() { var x = 1; }"),
+            vec!["unused-variable"]
+        );
+        assert_eq!(
+            default_codes("var a; fun f(a) { print a; }"),
+            vec!["shadowed-variable"]
+        );
+        assert!(default_codes("{ fun f() {} f(); }").is_empty());
+    }
+}
@@ -1,47 +1,92 @@
 use std::env;
+use std::io::{self, IsTerminal};
 use std::process;
 
 mod ast;
+mod ast_json;
+mod cli;
+mod cst;
+mod environment;
 mod error;
+mod formatter;
+mod graphviz;
 mod interpreter;
+mod json;
+mod lint;
+mod natives;
+mod optimizer;
 mod parser;
+mod repl;
+mod repl_config;
+mod repl_helper;
 mod scanner;
+mod sexpr;
 mod token;
+mod token_dump;
+mod value;
+mod visitor;
 
+use cli::Command;
 use error::RLoxError;
-use interpreter::{run_file, run_prompt};
+use interpreter::{check_file, run_file, Interpreter};
+use repl::run_prompt;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-
-    let err = if args.len() > 2 {
-        println!("Usage: rlox [script]");
-        process::exit(64);
-    } else if args.len() == 2 {
-        run_file(args[1].to_string())
-    } else {
-        run_prompt()
+    let options = match cli::parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => usage_error(&message),
     };
 
-    match err {
-        Err(err) => match err {
-            RLoxError::IO { .. } => {
-                eprintln!("IO error while trying to run file: {}", args[1].to_string());
-                process::exit(1);
-            }
-            src_error @ RLoxError::Source { .. } => {
-                eprintln!("{}", src_error);
-                process::exit(65);
-            }
-            RLoxError::Interrupted => {
-                eprintln!("Interrupted!");
-                process::exit(1);
-            }
-            RLoxError::EOF => {
-                eprintln!("Goodbye!");
-                process::exit(0);
-            }
+    let err = match options.command {
+        Command::Help => {
+            println!("{}", cli::USAGE);
+            Ok(())
+        }
+        Command::Version => {
+            println!("rlox {}", env!("CARGO_PKG_VERSION"));
+            Ok(())
+        }
+        Command::Run { path, args } => run_file(path, args, options.emit, options.optimize),
+        Command::Eval { code, args } => interpreter::run(
+            &mut Interpreter::with_args(args),
+            code,
+            options.emit,
+            options.optimize,
+        ),
+        Command::Default if !io::stdin().is_terminal() => {
+            run_file("-".to_string(), Vec::new(), options.emit, options.optimize)
+        }
+        // The REPL has no single program to emit or optimize.
+        Command::Default if options.emit.is_some() || options.optimize => {
+            usage_error("--emit and -O need a script or a program on standard input")
+        }
+        Command::Default | Command::Repl => run_prompt(),
+        Command::Check { path } => check_file(path),
+        Command::Lint { path } => match lint::lint_file(&path) {
+            Ok(errors) if errors > 0 => process::exit(1),
+            result => result.map(|_| ()),
+        },
+        Command::Fmt { path, check, width } => match formatter::format_file(&path, check, width) {
+            // Like `diff`, a check that finds changes exits with 1.
+            Ok(true) if check => process::exit(1),
+            result => result.map(|_| ()),
         },
-        Ok(()) => (),
+    };
+
+    if let Err(err) = err {
+        match err {
+            RLoxError::Exit { .. } => (),
+            RLoxError::Interrupted => eprintln!("Interrupted!"),
+            RLoxError::EOF => eprintln!("Goodbye!"),
+            ref err => eprintln!("{}", err),
+        }
+        process::exit(err.exit_code());
     }
 }
+
+/// Reports a command line that makes no sense and exits with the usage
+/// status from sysexits.h.
+fn usage_error(message: &str) -> ! {
+    eprintln!("rlox: {}\n\n{}", message, cli::USAGE);
+    process::exit(64);
+}
@@ -0,0 +1,158 @@
+use crate::environment::Environment;
+use crate::error::{RLoxError, RLoxResult};
+use crate::interpreter::Interpreter;
+use crate::value::{NativeFn, NativeFunction, Value};
+
+use std::env;
+use std::rc::Rc;
+
+/// Binds every native function in `environment`'s global scope.
+pub fn define_natives(environment: &mut Environment) {
+    let natives: [(&'static str, usize, NativeFn); 5] = [
+        ("arg", 1, arg),
+        ("argc", 0, argc),
+        ("args", 0, args),
+        ("env", 1, env_var),
+        ("exit", 1, exit),
+    ];
+
+    for &(name, arity, function) in natives.iter() {
+        let native = NativeFunction {
+            name,
+            arity,
+            function,
+        };
+        environment.define(name, Value::Native(Rc::new(native)));
+    }
+}
+
+/// `arg(i)`: the command-line argument at index `i`, counting from 0, or
+/// `nil` past the last one.
+fn arg(interpreter: &Interpreter, arguments: &[Value], line: Option<usize>) -> RLoxResult<Value> {
+    match arguments[0] {
+        Value::Number(i) if i.fract() == 0.0 && i >= 0.0 => Ok(interpreter
+            .args()
+            .get(i as usize)
+            .map_or(Value::Nil, |arg| Value::String(arg.clone()))),
+        _ => Err(RLoxError::runtime(
+            line,
+            "arg() expects a non-negative integer".to_string(),
+        )),
+    }
+}
+
+/// `argc()`: the number of command-line arguments.
+fn argc(
+    interpreter: &Interpreter,
+    _arguments: &[Value],
+    _line: Option<usize>,
+) -> RLoxResult<Value> {
+    Ok(Value::Number(interpreter.args().len() as f64))
+}
+
+/// `args()`: the command-line arguments that followed the script path.
+fn args(
+    interpreter: &Interpreter,
+    _arguments: &[Value],
+    _line: Option<usize>,
+) -> RLoxResult<Value> {
+    let args = interpreter
+        .args()
+        .iter()
+        .map(|arg| Value::String(arg.clone()))
+        .collect();
+    Ok(Value::List(Rc::new(args)))
+}
+
+/// `env(name)`: the value of an environment variable, or `nil` when it is
+/// not set.
+fn env_var(
+    _interpreter: &Interpreter,
+    arguments: &[Value],
+    line: Option<usize>,
+) -> RLoxResult<Value> {
+    match &arguments[0] {
+        Value::String(name) => Ok(env::var(name).map_or(Value::Nil, Value::String)),
+        _ => Err(RLoxError::runtime(
+            line,
+            "env() expects a string".to_string(),
+        )),
+    }
+}
+
+/// `exit(code)`: stops the program. It unwinds as an error, so callers can
+/// clean up before the process ends with `code`.
+fn exit(_interpreter: &Interpreter, arguments: &[Value], line: Option<usize>) -> RLoxResult<Value> {
+    match arguments[0] {
+        Value::Number(code) if code.fract() == 0.0 && (0.0..=255.0).contains(&code) => {
+            Err(RLoxError::Exit { code: code as i32 })
+        }
+        _ => Err(RLoxError::runtime(
+            line,
+            "exit() expects an integer from 0 to 255".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::RLoxError;
+    use crate::interpreter::{self, Interpreter};
+    use crate::value::Value;
+
+    fn eval(args: &[&str], src: &str) -> Result<Option<Value>, RLoxError> {
+        let args = args.iter().map(|arg| arg.to_string()).collect();
+        let ast = interpreter::parse_line(src.to_string())?;
+        Interpreter::with_args(args).interpret(ast)
+    }
+
+    fn string(s: &str) -> Option<Value> {
+        Some(Value::String(s.to_string()))
+    }
+
+    #[test]
+    fn scripts_read_their_arguments() {
+        assert_eq!(eval(&["a", "b"], "arg(1)").unwrap(), string("b"));
+        assert_eq!(
+            eval(&["a", "b"], "argc()").unwrap(),
+            Some(Value::Number(2.0))
+        );
+        assert_eq!(eval(&["a", "b"], "arg(argc() - 1)").unwrap(), string("b"));
+        assert_eq!(eval(&["a"], "arg(1)").unwrap(), Some(Value::Nil));
+        assert_eq!(eval(&[], "argc()").unwrap(), Some(Value::Number(0.0)));
+    }
+
+    #[test]
+    fn arg_needs_an_index() {
+        for src in &["arg(-1)", "arg(0.5)", "arg(\"0\")"] {
+            assert!(matches!(eval(&["a"], src), Err(RLoxError::Runtime { .. })));
+        }
+    }
+
+    #[test]
+    fn args_lists_every_argument() {
+        let value = eval(&["a", "b"], "args()").unwrap().unwrap();
+        assert_eq!(value.to_string(), "[a, b]");
+    }
+
+    #[test]
+    fn env_reads_variables() {
+        assert_eq!(
+            eval(&[], "env(\"RLOX_SURELY_UNSET_VARIABLE\")").unwrap(),
+            Some(Value::Nil)
+        );
+        assert!(eval(&[], "env(1)").is_err());
+    }
+
+    #[test]
+    fn exit_unwinds_with_its_code() {
+        assert!(matches!(
+            eval(&[], "exit(3)"),
+            Err(RLoxError::Exit { code: 3 })
+        ));
+        assert!(matches!(
+            eval(&[], "exit(300)"),
+            Err(RLoxError::Runtime { .. })
+        ));
+    }
+}
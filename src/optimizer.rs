@@ -0,0 +1,367 @@
+use crate::ast::{ASTNode, Ast, BlockNode, ExprId, LiteralNode, NodeId, StmtId, StmtNode};
+use crate::interpreter;
+use crate::token::TokenType;
+use crate::value::Value;
+use crate::visitor::{self, VisitorMut};
+
+/// Rewrites `ast` in place into a cheaper program that behaves the same.
+///
+/// Constant operands are folded with the interpreter's own operators, and
+/// only when that succeeds, so an expression such as `1 / "a"` is left
+/// alone to fail at runtime on its original line.
+///
+/// A `?:` or `if` with a constant condition is replaced by the branch it
+/// picks, so a dead branch is never compiled into the program.
+pub fn optimize(ast: &mut Ast) {
+    Optimizer.visit_program_mut(ast);
+}
+
+struct Optimizer;
+
+impl VisitorMut for Optimizer {
+    fn visit_binary_mut(&mut self, ast: &mut Ast, id: ExprId) {
+        visitor::walk_children_mut(self, ast, id);
+        let node = match ast.get(id) {
+            ASTNode::Binary(node) => node.clone(),
+            _ => return,
+        };
+
+        if let (Some(left), Some(right)) = (constant(ast, node.left()), constant(ast, node.right()))
+        {
+            if let Ok(value) = interpreter::binary_op(node.operator(), left, right) {
+                replace_with_value(ast, id, value);
+            }
+            return;
+        }
+
+        // `x * 1` is `x` only when `x` is known to be a number; otherwise
+        // the multiplication is what reports the type error.
+        if node.operator().token_type == TokenType::Star {
+            let is_one = |operand| constant(ast, operand) == Some(Value::Number(1.0));
+            let kept = if is_one(node.right()) {
+                node.left()
+            } else if is_one(node.left()) {
+                node.right()
+            } else {
+                return;
+            };
+            if is_number(ast, kept) {
+                let kept = ast.get(kept).clone();
+                ast.replace(id, kept);
+            }
+        }
+    }
+
+    /// A constant condition leaves only the branch it picks.
+    fn visit_conditional_mut(&mut self, ast: &mut Ast, id: ExprId) {
+        visitor::walk_children_mut(self, ast, id);
+        if let ASTNode::Conditional(node) = ast.get(id) {
+            if let Some(condition) = constant(ast, node.condition()) {
+                let branch = if condition.is_truthy() {
+                    node.then_branch()
+                } else {
+                    node.else_branch()
+                };
+                let branch = ast.get(branch).clone();
+                ast.replace(id, branch);
+            }
+        }
+    }
+
+    /// Like a constant `?:`, leaving an empty block when there is no `else`
+    /// to pick.
+    fn visit_if_stmt_mut(&mut self, ast: &mut Ast, id: StmtId) {
+        visitor::walk_stmt_children_mut(self, ast, id);
+        if let StmtNode::If(node) = ast.get_stmt(id) {
+            if let Some(condition) = constant(ast, node.condition()) {
+                let branch = if condition.is_truthy() {
+                    Some(node.then_branch())
+                } else {
+                    node.else_branch()
+                };
+                let branch = match branch {
+                    Some(branch) => ast.get_stmt(branch).clone(),
+                    None => StmtNode::Block(BlockNode::new(vec![])),
+                };
+                ast.replace_stmt(id, branch);
+            }
+        }
+    }
+
+    fn visit_grouping_mut(&mut self, ast: &mut Ast, id: ExprId) {
+        visitor::walk_children_mut(self, ast, id);
+        if let ASTNode::Grouping(node) = ast.get(id) {
+            if let ASTNode::Literal(_) = ast.get(node.child()) {
+                let literal = ast.get(node.child()).clone();
+                ast.replace(id, literal);
+            }
+        }
+    }
+
+    fn visit_interpolation_mut(&mut self, ast: &mut Ast, id: ExprId) {
+        visitor::walk_children_mut(self, ast, id);
+        let parts = match ast.get(id) {
+            ASTNode::Interpolation(node) => node.parts().to_vec(),
+            _ => return,
+        };
+
+        let mut joined = String::new();
+        for part in parts {
+            match constant(ast, part) {
+                Some(value) => joined.push_str(&value.to_string()),
+                None => return,
+            }
+        }
+        replace_with_value(ast, id, Value::String(joined));
+    }
+
+    fn visit_unary_mut(&mut self, ast: &mut Ast, id: ExprId) {
+        visitor::walk_children_mut(self, ast, id);
+        let node = match ast.get(id) {
+            ASTNode::Unary(node) => node.clone(),
+            _ => return,
+        };
+
+        if let Some(operand) = constant(ast, node.child()) {
+            if let Ok(value) = interpreter::unary_op(node.operator(), operand) {
+                replace_with_value(ast, id, value);
+            }
+            return;
+        }
+
+        // `!!x` is `x` when `x` is already a boolean, or when only its
+        // truthiness matters. That includes `!y`, so `!!!y` becomes `!y`.
+        if node.operator().token_type == TokenType::Bang {
+            if let Some(inner) = negated(ast, node.child()) {
+                if is_boolean(ast, inner) || in_boolean_context(ast, id) {
+                    let inner = ast.get(inner).clone();
+                    ast.replace(id, inner);
+                }
+            }
+        }
+    }
+}
+
+/// The value of `id` when it is a literal, looking through parentheses.
+fn constant(ast: &Ast, id: ExprId) -> Option<Value> {
+    match ast.get(id) {
+        ASTNode::Literal(node) => Some(interpreter::literal_value(node.value())),
+        ASTNode::Grouping(node) => constant(ast, node.child()),
+        _ => None,
+    }
+}
+
+/// The operand of `id` when it is a `!` expression.
+fn negated(ast: &Ast, id: ExprId) -> Option<ExprId> {
+    match ast.get(id) {
+        ASTNode::Unary(node) if node.operator().token_type == TokenType::Bang => Some(node.child()),
+        ASTNode::Grouping(node) => negated(ast, node.child()),
+        _ => None,
+    }
+}
+
+/// Whether only the truthiness of `id`'s value is used, as for a
+/// condition or the operand of `!`.
+fn in_boolean_context(ast: &Ast, id: ExprId) -> bool {
+    let parent = match ast.parent(id) {
+        Some(NodeId::Expr(parent)) => parent,
+        Some(NodeId::Stmt(parent)) => {
+            return match ast.get_stmt(parent) {
+                StmtNode::If(node) => node.condition() == id,
+                StmtNode::While(node) => node.condition() == id,
+                _ => false,
+            }
+        }
+        None => return false,
+    };
+    match ast.get(parent) {
+        ASTNode::Conditional(node) => node.condition() == id,
+        ASTNode::Unary(node) => node.operator().token_type == TokenType::Bang,
+        ASTNode::Grouping(_) => in_boolean_context(ast, parent),
+        _ => false,
+    }
+}
+
+/// Whether `id` can only evaluate to a boolean, if it evaluates at all.
+fn is_boolean(ast: &Ast, id: ExprId) -> bool {
+    match ast.get(id) {
+        ASTNode::Literal(node) => matches!(node.value(), TokenType::True | TokenType::False),
+        ASTNode::Grouping(node) => is_boolean(ast, node.child()),
+        ASTNode::Unary(node) => node.operator().token_type == TokenType::Bang,
+        ASTNode::Binary(node) => matches!(
+            node.operator().token_type,
+            TokenType::EqualEqual
+                | TokenType::BangEqual
+                | TokenType::Greater
+                | TokenType::GreaterEqual
+                | TokenType::Less
+                | TokenType::LessEqual
+        ),
+        _ => false,
+    }
+}
+
+/// Whether `id` can only evaluate to a number, if it evaluates at all.
+fn is_number(ast: &Ast, id: ExprId) -> bool {
+    match ast.get(id) {
+        ASTNode::Literal(node) => matches!(node.value(), TokenType::Number(_)),
+        ASTNode::Grouping(node) => is_number(ast, node.child()),
+        ASTNode::Unary(node) => node.operator().token_type == TokenType::Minus,
+        ASTNode::Increment(_) => true,
+        ASTNode::Binary(node) => matches!(
+            node.operator().token_type,
+            TokenType::Minus
+                | TokenType::Star
+                | TokenType::Slash
+                | TokenType::Percent
+                | TokenType::StarStar
+        ),
+        _ => false,
+    }
+}
+
+fn replace_with_value(ast: &mut Ast, id: ExprId, value: Value) {
+    let literal = match value {
+        Value::Number(n) => TokenType::Number(n),
+        Value::String(s) => TokenType::String(s),
+        Value::Boolean(true) => TokenType::True,
+        Value::Boolean(false) => TokenType::False,
+        Value::Nil => TokenType::Nil,
+        // Folding only ever combines literals, which cannot produce these.
+        _ => return,
+    };
+    ast.replace(id, ASTNode::Literal(LiteralNode::new(literal)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::StmtNode;
+    use crate::error::RLoxError;
+    use crate::interpreter::Interpreter;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+    use crate::sexpr;
+
+    fn parse(src: &str) -> Ast {
+        let tokens = Scanner::new(src.to_string())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        Parser::new(tokens).parse().unwrap()
+    }
+
+    fn optimized(src: &str) -> Ast {
+        let mut ast = parse(src);
+        optimize(&mut ast);
+        ast
+    }
+
+    fn sexpr(src: &str) -> String {
+        sexpr::to_sexpr(&optimized(src))
+    }
+
+    #[test]
+    fn folds_constant_operands() {
+        assert_eq!(sexpr("print (1 + 2) * 3;"), "(print 9)");
+        assert_eq!(sexpr("print 2 ** 3 % 5;"), "(print 3)");
+        assert_eq!(sexpr("print -(4 - 5);"), "(print 1)");
+        assert_eq!(sexpr("print !nil;"), "(print true)");
+        assert_eq!(sexpr("print \"a\" + \"b\";"), "(print \"ab\")");
+        assert_eq!(sexpr("print \"s${1 + 1}\";"), "(print \"s2\")");
+        assert_eq!(sexpr("print 1 < 2 == true;"), "(print true)");
+    }
+
+    #[test]
+    fn keeps_operations_that_would_fail() {
+        assert_eq!(sexpr("print 1 / \"a\";"), "(print (/ 1 \"a\"))");
+        assert_eq!(sexpr("print -\"a\";"), "(print (- \"a\"))");
+        assert_eq!(sexpr("print \"a\" * 1;"), "(print (* \"a\" 1))");
+    }
+
+    #[test]
+    fn unfoldable_operations_still_fail_on_their_line() {
+        let ast = optimized("var x = 1 + 2;\nprint x;\nprint 1 / \"a\";");
+        let result = Interpreter::new().interpret(ast);
+        assert!(matches!(
+            result,
+            Err(RLoxError::Runtime { line: Some(3), .. })
+        ));
+    }
+
+    #[test]
+    fn constant_conditions_pick_a_branch() {
+        assert_eq!(sexpr("var x; print true ? x : 0;"), "(var x)\n(print x)");
+        assert_eq!(sexpr("var x; print nil ? 0 : x;"), "(var x)\n(print x)");
+        assert_eq!(
+            sexpr("var x; print x ? 1 : 2;"),
+            "(var x)\n(print (?: x 1 2))"
+        );
+    }
+
+    #[test]
+    fn simplifies_identities() {
+        assert_eq!(
+            sexpr("var x; print !!(x < 1);"),
+            "(var x)\n(print (group (< x 1)))"
+        );
+        assert_eq!(sexpr("var x; print !!x;"), "(var x)\n(print (! (! x)))");
+        assert_eq!(sexpr("var x; print -x * 1;"), "(var x)\n(print (- x))");
+        assert_eq!(sexpr("var x; print x * 1;"), "(var x)\n(print (* x 1))");
+    }
+
+    #[test]
+    fn double_negation_in_boolean_contexts() {
+        let declare = "var x; var a; var b;\n";
+        let optimized = |src: &str| {
+            let sexpr = sexpr(&format!("{}{}", declare, src));
+            sexpr.lines().last().unwrap().to_string()
+        };
+        assert_eq!(optimized("print !!x ? a : b;"), "(print (?: x a b))");
+        assert_eq!(
+            optimized("print (!!x) ? a : b;"),
+            "(print (?: (group x) a b))"
+        );
+        assert_eq!(optimized("print !(!!x);"), "(print (! (group x)))");
+        // Elsewhere the value itself is used, and `!!x` makes it a boolean.
+        assert_eq!(
+            optimized("print a ? !!x : b;"),
+            "(print (?: a (! (! x)) b))"
+        );
+        assert_eq!(optimized("a = !!x;"), "(expr (= a (! (! x))))");
+        assert_eq!(optimized("if (!!x) print a;"), "(if x (print a))");
+        assert_eq!(optimized("while (!!x) print a;"), "(while x (print a))");
+        assert_eq!(optimized("print !!x;"), "(print (! (! x)))");
+    }
+
+    #[test]
+    fn constant_if_statements_keep_one_branch() {
+        assert_eq!(sexpr("if (true) print 1; else print 2;"), "(print 1)");
+        assert_eq!(
+            sexpr("if (1 > 2) print 1; else { print 2; }"),
+            "(block (print 2))"
+        );
+        assert_eq!(sexpr("if (false) print 1;"), "(block)");
+        assert_eq!(
+            sexpr("fun f() { if (nil) return 1; return 2; }"),
+            "(fun f () (block (block) (return 2)))"
+        );
+        assert_eq!(sexpr("var x; if (x) print 1;"), "(var x)\n(if x (print 1))");
+    }
+
+    #[test]
+    fn replaced_children_are_detached() {
+        let mut ast = parse("print 1 + 2;");
+        let sum = match ast.get_stmt(ast.statements()[0]) {
+            StmtNode::Print(node) => node.expression(),
+            _ => unreachable!(),
+        };
+        let operands = ast.get(sum).children();
+        optimize(&mut ast);
+
+        assert!(matches!(ast.get(sum), ASTNode::Literal(_)));
+        assert_eq!(operands.len(), 2);
+        for operand in operands {
+            assert_eq!(ast.parent(operand), None);
+        }
+    }
+}
@@ -1,48 +1,372 @@
-use crate::ast::{ASTNode, BinaryNode, GroupingNode, LiteralNode, UnaryNode};
+use crate::ast::{
+    ASTNode, AssignNode, Ast, BinaryNode, BlockNode, CallNode, ConditionalNode, ExprId,
+    ExpressionNode, FunctionNode, GroupingNode, IfNode, IncrementNode, InterpolationNode,
+    LiteralNode, PrintNode, ReturnNode, StmtId, StmtNode, UnaryNode, VarNode, VariableNode,
+    WhileNode,
+};
 use crate::error::{RLoxError, RLoxResult};
-use crate::token::{Token, TokenType};
+use crate::token::{Span, Token, TokenType};
+
+/// Calls take at most this many arguments, and functions this many
+/// parameters, as in clox.
+const MAX_ARGUMENTS: usize = 255;
+
+/// Binary operator levels, loosest first, used to recover from a missing
+/// left operand.
+enum Precedence {
+    Comma,
+    Equality,
+    Comparison,
+    Addition,
+    Multiplication,
+    Exponent,
+}
+
+/// The level of the binary operator `token_type`, if it is one. `-` is
+/// left out since it is also a prefix operator.
+fn binary_precedence(token_type: &TokenType) -> Option<Precedence> {
+    match token_type {
+        TokenType::Comma => Some(Precedence::Comma),
+        TokenType::BangEqual | TokenType::EqualEqual => Some(Precedence::Equality),
+        TokenType::Greater | TokenType::GreaterEqual | TokenType::Less | TokenType::LessEqual => {
+            Some(Precedence::Comparison)
+        }
+        TokenType::Plus => Some(Precedence::Addition),
+        TokenType::Slash | TokenType::Star | TokenType::Percent => Some(Precedence::Multiplication),
+        TokenType::StarStar => Some(Precedence::Exponent),
+        _ => None,
+    }
+}
+
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    ast: Ast,
+    error: Option<RLoxError>,
+    allow_bare_expression: bool,
+    /// How many function bodies the parser is inside, so that `return`
+    /// can be rejected outside of one.
+    function_depth: usize,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>) -> Self {
-        Parser { tokens, current: 0 }
+        Parser {
+            tokens,
+            current: 0,
+            ast: Ast::new(),
+            error: None,
+            allow_bare_expression: false,
+            function_depth: 0,
+        }
+    }
+
+    /// Lets the last statement be an expression without a trailing `;`, as
+    /// typed at the REPL.
+    pub fn allow_bare_expression(mut self) -> Self {
+        self.allow_bare_expression = true;
+        self
+    }
+
+    /// Parses the whole token stream. Returns the first syntax error, if
+    /// there was one.
+    ///
+    /// Grammar:
+    ///     program -> declaration* EOF
+    pub fn parse(mut self) -> RLoxResult<Ast> {
+        while !self.is_at_end() && self.error.is_none() {
+            match self.declaration() {
+                Ok(stmt) => self.ast.push_statement(stmt),
+                Err(_) => break,
+            }
+        }
+
+        match self.error {
+            Some(error) => Err(error),
+            None => Ok(self.ast),
+        }
+    }
+
+    /// Grammar:
+    ///     declaration -> funDecl | varDecl | statement
+    fn declaration(&mut self) -> RLoxResult<StmtId> {
+        if self.match_any(&[TokenType::Fun]) {
+            self.function()
+        } else if self.match_any(&[TokenType::Var]) {
+            self.var_declaration()
+        } else {
+            self.statement()
+        }
+    }
+
+    /// Grammar:
+    ///     funDecl -> "fun" IDENTIFIER "(" parameters? ")" block
+    ///     parameters -> IDENTIFIER ( "," IDENTIFIER )*
+    fn function(&mut self) -> RLoxResult<StmtId> {
+        let start = self.current - 1;
+        let name = self.consume_identifier("expected function name")?;
+        self.consume(&TokenType::LeftParen, "expected '(' after function name")?;
+
+        let mut params = Vec::new();
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                if params.len() >= MAX_ARGUMENTS {
+                    let token = self.peek();
+                    self.report(&token, "can't have more than 255 parameters");
+                }
+                params.push(self.consume_identifier("expected parameter name")?);
+                if !self.match_any(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(&TokenType::RightParen, "expected ')' after parameters")?;
+
+        let body_start = self.current;
+        self.consume(&TokenType::LeftBrace, "expected '{' before function body")?;
+        self.function_depth += 1;
+        let statements = self.block();
+        self.function_depth -= 1;
+        let body = self.stmt(StmtNode::Block(BlockNode::new(statements?)), body_start);
+
+        let node = FunctionNode::new(name, params, body);
+        Ok(self.stmt(StmtNode::Function(node), start))
+    }
+
+    /// The initializer stops at a top-level comma, as in C, so
+    /// `var a = 1, b;` is an error rather than `var a = (1, b);`.
+    ///
+    /// Grammar:
+    ///     varDecl -> "var" IDENTIFIER ( "=" assignment )? ";"
+    fn var_declaration(&mut self) -> RLoxResult<StmtId> {
+        let start = self.current - 1;
+        let name = self.consume_identifier("expected variable name")?;
+
+        let initializer = if self.match_any(&[TokenType::Equal]) {
+            Some(self.assignment())
+        } else {
+            None
+        };
+
+        self.consume(
+            &TokenType::Semicolon,
+            "expected ';' after variable declaration",
+        )?;
+        Ok(self.stmt(StmtNode::Var(VarNode::new(name, initializer)), start))
+    }
+
+    /// Grammar:
+    ///     statement -> exprStmt | ifStmt | printStmt | returnStmt | whileStmt
+    ///                | block
+    fn statement(&mut self) -> RLoxResult<StmtId> {
+        let start = self.current;
+
+        if self.match_any(&[TokenType::If]) {
+            return self.if_statement();
+        }
+
+        if self.match_any(&[TokenType::Return]) {
+            return self.return_statement();
+        }
+
+        if self.match_any(&[TokenType::While]) {
+            return self.while_statement();
+        }
+
+        if self.match_any(&[TokenType::Print]) {
+            let expression = self.expression();
+            self.consume(&TokenType::Semicolon, "expected ';' after value")?;
+            return Ok(self.stmt(StmtNode::Print(PrintNode::new(expression)), start));
+        }
+
+        if self.match_any(&[TokenType::LeftBrace]) {
+            let statements = self.block()?;
+            return Ok(self.stmt(StmtNode::Block(BlockNode::new(statements)), start));
+        }
+
+        let expression = self.expression();
+        if !(self.allow_bare_expression && self.is_at_end()) {
+            self.consume(&TokenType::Semicolon, "expected ';' after expression")?;
+        }
+        Ok(self.stmt(StmtNode::Expression(ExpressionNode::new(expression)), start))
+    }
+
+    /// An `else` belongs to the nearest `if`.
+    ///
+    /// Grammar:
+    ///     ifStmt -> "if" "(" expression ")" statement ( "else" statement )?
+    fn if_statement(&mut self) -> RLoxResult<StmtId> {
+        let start = self.current - 1;
+        let condition = self.condition("if")?;
+        let then_branch = self.statement()?;
+        let else_branch = if self.match_any(&[TokenType::Else]) {
+            Some(self.statement()?)
+        } else {
+            None
+        };
+
+        let node = IfNode::new(condition, then_branch, else_branch);
+        Ok(self.stmt(StmtNode::If(node), start))
+    }
+
+    /// Grammar:
+    ///     returnStmt -> "return" expression? ";"
+    fn return_statement(&mut self) -> RLoxResult<StmtId> {
+        let start = self.current - 1;
+        let keyword = self.previous();
+        if self.function_depth == 0 {
+            self.report(&keyword, "can't return from top-level code");
+        }
+
+        let value = if self.check(&TokenType::Semicolon) {
+            None
+        } else {
+            Some(self.expression())
+        };
+        self.consume(&TokenType::Semicolon, "expected ';' after return value")?;
+        Ok(self.stmt(StmtNode::Return(ReturnNode::new(value)), start))
     }
 
-    pub fn parse(&mut self) -> ASTNode {
-        self.expression()
+    /// Grammar:
+    ///     whileStmt -> "while" "(" expression ")" statement
+    fn while_statement(&mut self) -> RLoxResult<StmtId> {
+        let start = self.current - 1;
+        let condition = self.condition("while")?;
+        let body = self.statement()?;
+        Ok(self.stmt(StmtNode::While(WhileNode::new(condition, body)), start))
+    }
+
+    /// Parses the parenthesized condition of an `if` or `while`.
+    fn condition(&mut self, keyword: &str) -> RLoxResult<ExprId> {
+        self.consume(
+            &TokenType::LeftParen,
+            &format!("expected '(' after '{}'", keyword),
+        )?;
+        let condition = self.expression();
+        self.consume(
+            &TokenType::RightParen,
+            &format!("expected ')' after {} condition", keyword),
+        )?;
+        Ok(condition)
+    }
+
+    /// Parses the statements of a block, after its opening brace.
+    ///
+    /// Grammar:
+    ///     block -> "{" declaration* "}"
+    fn block(&mut self) -> RLoxResult<Vec<StmtId>> {
+        let mut statements = Vec::new();
+
+        while !self.check(&TokenType::RightBrace) && !self.is_at_end() {
+            statements.push(self.declaration()?);
+        }
+
+        self.consume(&TokenType::RightBrace, "expected '}' after block")?;
+        Ok(statements)
     }
 
     /// Top level rule for parsing expressions.
     ///
     /// Grammar:
-    ///     expression -> equality
-    fn expression(&mut self) -> ASTNode {
-        self.equality()
+    ///     expression -> comma
+    fn expression(&mut self) -> ExprId {
+        self.comma()
+    }
+
+    /// Evaluates both operands and gives the right one, as in C.
+    ///
+    /// Grammar:
+    ///     comma -> assignment ( "," assignment )*
+    fn comma(&mut self) -> ExprId {
+        let mut expr = self.assignment();
+
+        while self.match_any(&[TokenType::Comma]) {
+            let operator = self.previous();
+            let right = self.assignment();
+            expr = self.binary(expr, operator, right);
+        }
+
+        expr
+    }
+
+    /// Compound assignments such as `a += b` keep their operator, rather
+    /// than becoming `a = a + b`, so errors and tools see what was written.
+    ///
+    /// Grammar:
+    ///     assignment -> IDENTIFIER ( "=" | "+=" | "-=" | "*=" | "/=" | "%=" ) assignment
+    ///                 | conditional
+    fn assignment(&mut self) -> ExprId {
+        let expr = self.conditional();
+
+        if self.match_any(&[
+            TokenType::Equal,
+            TokenType::PlusEqual,
+            TokenType::MinusEqual,
+            TokenType::StarEqual,
+            TokenType::SlashEqual,
+            TokenType::PercentEqual,
+        ]) {
+            let equals = self.previous();
+            let value = self.assignment();
+
+            if let ASTNode::Variable(target) = self.ast.get(expr) {
+                let node = AssignNode::new(target.name().clone(), equals, value);
+                let span = self.ast.span(expr).to(self.ast.span(value));
+                return self.ast.add(ASTNode::Assign(node), span);
+            }
+
+            self.report(&equals, "invalid assignment target");
+        }
+
+        expr
+    }
+
+    /// The conditional operator, which nests to the right, so
+    /// `a ? b : c ? d : e` is `a ? b : (c ? d : e)`.
+    ///
+    /// Grammar:
+    ///     conditional -> equality ( "?" expression ":" conditional )?
+    fn conditional(&mut self) -> ExprId {
+        let condition = self.equality();
+
+        if self.match_any(&[TokenType::Question]) {
+            let then_branch = self.expression();
+            if self
+                .consume(&TokenType::Colon, "expected ':' after then branch of '?'")
+                .is_err()
+            {
+                return self.ast.add(ASTNode::Error, self.peek().span);
+            }
+            let else_branch = self.conditional();
+
+            let span = self.ast.span(condition).to(self.ast.span(else_branch));
+            let node = ConditionalNode::new(condition, then_branch, else_branch);
+            return self.ast.add(ASTNode::Conditional(node), span);
+        }
+
+        condition
     }
 
     /// Matches the equality testing operation.
     ///
     /// Grammar:
     ///     equality -> comparison ( ("!=" | "==") comparison )*
-    fn equality(&mut self) -> ASTNode {
+    fn equality(&mut self) -> ExprId {
         let mut expr = self.comparison();
 
-        while self.match_any(&vec![TokenType::BangEqual, TokenType::EqualEqual]) {
+        while self.match_any(&[TokenType::BangEqual, TokenType::EqualEqual]) {
             let operator = self.previous();
             let right = self.comparison();
-            expr = ASTNode::Binary(BinaryNode::new(expr, operator.clone(), right));
+            expr = self.binary(expr, operator, right);
         }
 
         expr
     }
 
-    fn comparison(&mut self) -> ASTNode {
+    fn comparison(&mut self) -> ExprId {
         let mut expr = self.addition();
 
-        while self.match_any(&vec![
+        while self.match_any(&[
             TokenType::Greater,
             TokenType::GreaterEqual,
             TokenType::Less,
@@ -50,70 +374,196 @@ impl Parser {
         ]) {
             let operator = self.previous();
             let right = self.addition();
-            expr = ASTNode::Binary(BinaryNode::new(expr, operator.clone(), right));
+            expr = self.binary(expr, operator, right);
         }
 
         expr
     }
 
-    fn addition(&mut self) -> ASTNode {
+    fn addition(&mut self) -> ExprId {
         let mut expr = self.multiplication();
 
-        while self.match_any(&vec![TokenType::Minus, TokenType::Plus]) {
+        while self.match_any(&[TokenType::Minus, TokenType::Plus]) {
             let operator = self.previous();
             let right = self.multiplication();
-            expr = ASTNode::Binary(BinaryNode::new(expr, operator.clone(), right));
+            expr = self.binary(expr, operator, right);
         }
 
         expr
     }
 
-    fn multiplication(&mut self) -> ASTNode {
+    fn multiplication(&mut self) -> ExprId {
         let mut expr = self.unary();
 
-        while self.match_any(&vec![TokenType::Slash, TokenType::Star]) {
+        while self.match_any(&[TokenType::Slash, TokenType::Star, TokenType::Percent]) {
             let operator = self.previous();
             let right = self.unary();
-            expr = ASTNode::Binary(BinaryNode::new(expr, operator.clone(), right));
+            expr = self.binary(expr, operator, right);
         }
 
         expr
     }
 
-    fn unary(&mut self) -> ASTNode {
-        if self.match_any(&vec![TokenType::Bang, TokenType::Minus]) {
+    /// Grammar:
+    ///     unary -> ( "!" | "-" ) unary | exponent
+    fn unary(&mut self) -> ExprId {
+        if self.match_any(&[TokenType::Bang, TokenType::Minus]) {
             let operator = self.previous();
             let right = self.unary();
-            return ASTNode::Unary(UnaryNode::new(operator.clone(), right));
+            let span = operator.span.to(self.ast.span(right));
+            return self
+                .ast
+                .add(ASTNode::Unary(UnaryNode::new(operator, right)), span);
         }
 
-        self.primary()
+        self.exponent()
     }
 
-    fn primary(&mut self) -> ASTNode {
-        if self.match_any(&vec![TokenType::False]) {
+    /// Exponentiation nests to the right and binds tighter than `-` or `!`
+    /// on its left, so `-2 ** 2` is `-(2 ** 2)`. An increment is an
+    /// operand, so `++x ** 2` is `(++x) ** 2`.
+    ///
+    /// Grammar:
+    ///     exponent -> prefix ( "**" unary )?
+    fn exponent(&mut self) -> ExprId {
+        let expr = self.prefix();
+
+        if self.match_any(&[TokenType::StarStar]) {
+            let operator = self.previous();
+            let right = self.unary();
+            return self.binary(expr, operator, right);
+        }
+
+        expr
+    }
+
+    /// Grammar:
+    ///     prefix -> ( "++" | "--" ) prefix | postfix
+    fn prefix(&mut self) -> ExprId {
+        if self.match_any(&[TokenType::PlusPlus, TokenType::MinusMinus]) {
+            let operator = self.previous();
+            let target = self.prefix();
+            let span = operator.span.to(self.ast.span(target));
+            return self.increment(operator, target, true, span);
+        }
+
+        self.postfix()
+    }
+
+    /// Grammar:
+    ///     postfix -> call ( "++" | "--" )?
+    fn postfix(&mut self) -> ExprId {
+        let expr = self.call();
+
+        if self.match_any(&[TokenType::PlusPlus, TokenType::MinusMinus]) {
+            let operator = self.previous();
+            let span = self.ast.span(expr).to(operator.span);
+            return self.increment(operator, expr, false, span);
+        }
+
+        expr
+    }
+
+    /// Adds an increment or decrement of `target`, which has to be a
+    /// variable.
+    fn increment(&mut self, operator: Token, target: ExprId, prefix: bool, span: Span) -> ExprId {
+        match self.ast.get(target) {
+            ASTNode::Variable(variable) => {
+                let node = IncrementNode::new(operator, variable.name().clone(), prefix);
+                self.ast.add(ASTNode::Increment(node), span)
+            }
+            _ => {
+                let message = format!("invalid '{}' target", operator.token_type);
+                self.report(&operator, &message);
+                target
+            }
+        }
+    }
+
+    /// Grammar:
+    ///     call -> primary ( "(" arguments? ")" )*
+    fn call(&mut self) -> ExprId {
+        let mut expr = self.primary();
+
+        while self.match_any(&[TokenType::LeftParen]) {
+            expr = self.finish_call(expr);
+        }
+
+        expr
+    }
+
+    /// Parses the arguments of a call, after its opening parenthesis.
+    ///
+    /// Grammar:
+    ///     arguments -> assignment ( "," assignment )*
+    fn finish_call(&mut self, callee: ExprId) -> ExprId {
+        let mut arguments = Vec::new();
+
+        if !self.check(&TokenType::RightParen) {
+            loop {
+                if arguments.len() >= MAX_ARGUMENTS {
+                    let token = self.peek();
+                    self.report(&token, "can't have more than 255 arguments");
+                }
+                // Commas here separate arguments rather than being operators.
+                arguments.push(self.assignment());
+                if !self.match_any(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+
+        let paren = match self.consume(&TokenType::RightParen, "expected ')' after arguments") {
+            Ok(paren) => paren,
+            Err(_) => return self.ast.add(ASTNode::Error, self.peek().span),
+        };
+        let span = self.ast.span(callee).to(paren.span);
+        self.ast
+            .add(ASTNode::Call(CallNode::new(callee, paren, arguments)), span)
+    }
+
+    fn primary(&mut self) -> ExprId {
+        let start = self.current;
+        let node = self.primary_node();
+        let span = if self.current > start {
+            self.tokens[start].span.to(self.previous().span)
+        } else {
+            self.peek().span
+        };
+        self.ast.add(node, span)
+    }
+
+    fn primary_node(&mut self) -> ASTNode {
+        if self.match_any(&[TokenType::False]) {
             return ASTNode::Literal(LiteralNode::new(TokenType::False));
         }
 
-        if self.match_any(&vec![TokenType::True]) {
+        if self.match_any(&[TokenType::True]) {
             return ASTNode::Literal(LiteralNode::new(TokenType::True));
         }
 
-        if self.match_any(&vec![TokenType::Nil]) {
+        if self.match_any(&[TokenType::Nil]) {
             return ASTNode::Literal(LiteralNode::new(TokenType::Nil));
         }
 
         if self.is_at_end() {
+            let token = self.peek();
+            self.report(&token, "expected expression");
             return ASTNode::Error;
         }
 
         let next = self.advance();
+        if let TokenType::Identifier(_) = next.token_type {
+            return ASTNode::Variable(VariableNode::new(next));
+        }
+
         match next.token_type {
             TokenType::False => ASTNode::Literal(LiteralNode::new(TokenType::False)),
             TokenType::True => ASTNode::Literal(LiteralNode::new(TokenType::True)),
             TokenType::Nil => ASTNode::Literal(LiteralNode::new(TokenType::Nil)),
             TokenType::Number(n) => ASTNode::Literal(LiteralNode::new(TokenType::Number(n))),
             TokenType::String(s) => ASTNode::Literal(LiteralNode::new(TokenType::String(s))),
+            TokenType::Interpolation(s) => self.interpolation(s, next.span),
             TokenType::LeftParen => {
                 let expr = self.expression();
                 let next = self.consume(&TokenType::RightParen, "expected ')' after expression");
@@ -123,10 +573,105 @@ impl Parser {
                     ASTNode::Grouping(GroupingNode::new(expr))
                 }
             }
-            _ => ASTNode::Error,
+            _ if binary_precedence(&next.token_type).is_some() => self.missing_left_operand(next),
+            _ => {
+                self.report(&next, "expected expression");
+                ASTNode::Error
+            }
+        }
+    }
+
+    /// Reports a binary operator found where an expression should start,
+    /// as in `== 1`, then parses and discards its right operand so that
+    /// parsing carries on after it.
+    fn missing_left_operand(&mut self, operator: Token) -> ASTNode {
+        let message = format!("expected left operand before '{}'", operator.token_type);
+        self.report(&operator, &message);
+
+        match binary_precedence(&operator.token_type) {
+            Some(Precedence::Comma) => self.assignment(),
+            Some(Precedence::Equality) => self.comparison(),
+            Some(Precedence::Comparison) => self.addition(),
+            Some(Precedence::Addition) => self.multiplication(),
+            Some(Precedence::Multiplication) | Some(Precedence::Exponent) | None => self.unary(),
+        };
+        ASTNode::Error
+    }
+
+    /// Parses the rest of an interpolated string whose first piece, up to
+    /// the first `${`, was `first`.
+    ///
+    /// Grammar:
+    ///     interpolation -> ( INTERPOLATION expression )+ STRING
+    fn interpolation(&mut self, first: String, span: Span) -> ASTNode {
+        let mut parts = Vec::new();
+        self.push_segment(&mut parts, first, span);
+
+        loop {
+            if self.closes_interpolation(&self.peek()) {
+                // Point at the `}` rather than the rest of the string.
+                let token = Token {
+                    lexeme: Some("}".to_string()),
+                    ..self.peek()
+                };
+                self.report(&token, "expected expression inside '${}'");
+                return ASTNode::Error;
+            }
+            parts.push(self.expression());
+
+            let next = self.peek();
+            match next.token_type {
+                TokenType::Interpolation(s) => {
+                    self.advance();
+                    self.push_segment(&mut parts, s, next.span);
+                }
+                TokenType::String(s) => {
+                    self.advance();
+                    self.push_segment(&mut parts, s, next.span);
+                    return ASTNode::Interpolation(InterpolationNode::new(parts));
+                }
+                _ => {
+                    self.report(&next, "expected '}' after interpolated expression");
+                    return ASTNode::Error;
+                }
+            }
         }
     }
 
+    /// Whether `token` is the piece of string that follows an embedded
+    /// expression, which the scanner starts at the closing `}`.
+    fn closes_interpolation(&self, token: &Token) -> bool {
+        matches!(
+            token.token_type,
+            TokenType::String(_) | TokenType::Interpolation(_)
+        ) && token.lexeme.as_deref().is_some_and(|l| l.starts_with('}'))
+    }
+
+    /// Adds a literal piece of an interpolated string, unless it is empty.
+    fn push_segment(&mut self, parts: &mut Vec<ExprId>, segment: String, span: Span) {
+        if !segment.is_empty() {
+            let literal = LiteralNode::new(TokenType::String(segment));
+            parts.push(self.ast.add(ASTNode::Literal(literal), span));
+        }
+    }
+
+    /// Adds a statement spanning from the token at index `start` to the
+    /// last token consumed.
+    fn stmt(&mut self, node: StmtNode, start: usize) -> StmtId {
+        let span = self.tokens[start].span.to(self.previous().span);
+        self.ast.add_stmt(node, span)
+    }
+
+    /// Adds a binary node spanning from the start of `left` to the end of
+    /// `right`.
+    fn binary(&mut self, left: ExprId, operator: Token, right: ExprId) -> ExprId {
+        let span = self.ast.span(left).to(self.ast.span(right));
+        self.ast.add(
+            ASTNode::Binary(BinaryNode::new(left, operator, right)),
+            span,
+        )
+    }
+
     /// If the next token is any one of the tokens in `token_types`, returns true
     /// and advances to the next token in the stream.
     fn match_any(&mut self, token_types: &[TokenType]) -> bool {
@@ -137,7 +682,7 @@ impl Parser {
             }
         }
 
-        return false;
+        false
     }
 
     /// If the next token in the stream is the token that was passed in,
@@ -155,7 +700,7 @@ impl Parser {
     /// Return the last token we saw.
     fn advance(&mut self) -> Token {
         if !self.is_at_end() {
-            self.current = self.current + 1;
+            self.current += 1;
         }
 
         self.previous()
@@ -185,10 +730,274 @@ impl Parser {
         }
 
         let token = self.peek();
-        Err(RLoxError::Source {
-            line: token.line,
-            context: token.lexeme,
-            message: message.to_string(),
-        })
+        let error = self.error_at(&token, message);
+        self.report(&token, message);
+        Err(error)
+    }
+
+    /// Like `consume`, for an identifier with any name.
+    fn consume_identifier(&mut self, message: &str) -> RLoxResult<Token> {
+        if let TokenType::Identifier(_) = self.peek().token_type {
+            return Ok(self.advance());
+        }
+
+        let token = self.peek();
+        let error = self.error_at(&token, message);
+        self.report(&token, message);
+        Err(error)
+    }
+
+    /// Builds the error for `message` at `token`. Errors at the end of the
+    /// stream become `RLoxError::UnexpectedEOF` so callers can tell
+    /// incomplete input apart from invalid input.
+    fn error_at(&self, token: &Token, message: &str) -> RLoxError {
+        if token.token_type == TokenType::EOF {
+            RLoxError::UnexpectedEOF {
+                line: token.line,
+                message: message.to_string(),
+            }
+        } else {
+            RLoxError::source(token.line, token.lexeme.clone(), message.to_string())
+        }
+    }
+
+    /// Records a syntax error. Only the first one is kept, since later
+    /// errors are usually caused by the first.
+    fn report(&mut self, token: &Token, message: &str) {
+        if self.error.is_none() {
+            self.error = Some(self.error_at(token, message));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::Scanner;
+    use crate::sexpr;
+
+    fn parse(src: &str) -> RLoxResult<Ast> {
+        let tokens = Scanner::new(src.to_string()).collect::<RLoxResult<Vec<Token>>>()?;
+        Parser::new(tokens).parse()
+    }
+
+    /// The s-expression form of `src`, one line per statement.
+    fn sexpr(src: &str) -> String {
+        sexpr::to_sexpr(&parse(src).unwrap())
+    }
+
+    /// The message and context of the syntax error in `src`.
+    fn error(src: &str) -> (String, Option<String>) {
+        match parse(src) {
+            Err(RLoxError::Source {
+                message, context, ..
+            }) => (message, context),
+            other => panic!("expected a syntax error, got {:?}", other),
+        }
+    }
+
+    /// Parses one expression from `src` and returns the token the parser
+    /// stopped at, along with the first error.
+    fn stop(src: &str) -> (TokenType, Option<String>) {
+        let tokens = Scanner::new(src.to_string())
+            .collect::<RLoxResult<Vec<Token>>>()
+            .unwrap();
+        let mut parser = Parser::new(tokens);
+        parser.expression();
+        let next = parser.peek().token_type;
+        let message = parser.error.map(|error| match error {
+            RLoxError::Source { message, .. } => message,
+            other => panic!("expected a syntax error, got {:?}", other),
+        });
+        (next, message)
+    }
+
+    #[test]
+    fn conditionals_nest_to_the_right() {
+        assert_eq!(sexpr("a ? b : c;"), "(expr (?: a b c))");
+        assert_eq!(sexpr("a ? b : c ? d : e;"), "(expr (?: a b (?: c d e)))");
+        assert_eq!(sexpr("a ? b ? c : d : e;"), "(expr (?: a (?: b c d) e))");
+        assert_eq!(sexpr("a == b ? c : d;"), "(expr (?: (== a b) c d))");
+        // The middle operand is a full expression, the last is not.
+        assert_eq!(sexpr("a ? b, c : d;"), "(expr (?: a (, b c) d))");
+        assert_eq!(sexpr("a ? b : c, d;"), "(expr (, (?: a b c) d))");
+        assert_eq!(sexpr("x = a ? b : c;"), "(expr (= x (?: a b c)))");
+    }
+
+    #[test]
+    fn conditional_needs_a_colon() {
+        assert_eq!(
+            error("print a ? b;").0,
+            "expected ':' after then branch of '?'"
+        );
+    }
+
+    #[test]
+    fn comma_is_the_loosest_operator() {
+        assert_eq!(sexpr("a, b = c, d;"), "(expr (, (, a (= b c)) d))");
+        assert_eq!(sexpr("f(a, b);"), "(expr (call f a b))");
+        assert_eq!(sexpr("f((a, b));"), "(expr (call f (group (, a b))))");
+    }
+
+    #[test]
+    fn initializers_stop_at_a_comma() {
+        assert_eq!(sexpr("var a = (1, 2);"), "(var a (group (, 1 2)))");
+        assert_eq!(sexpr("var a = b = 1;"), "(var a (= b 1))");
+        assert_eq!(
+            error("var a = 1, b;").0,
+            "expected ';' after variable declaration"
+        );
+    }
+
+    #[test]
+    fn missing_left_operands_are_reported() {
+        for (src, operator) in &[("== 1;", "=="), ("+ 3;", "+"), ("* 2;", "*"), (", a;", ",")] {
+            let message = format!("expected left operand before '{}'", operator);
+            assert_eq!(error(src).0, message);
+        }
+        // `-` is a prefix operator, so `- 3` is fine.
+        assert_eq!(sexpr("- 3;"), "(expr (- 3))");
+    }
+
+    #[test]
+    fn missing_left_operands_discard_the_right_operand() {
+        let expected = |operator: &str| {
+            (
+                TokenType::Semicolon,
+                Some(format!("expected left operand before '{}'", operator)),
+            )
+        };
+        // The right operand is parsed at the operator's own level, so it
+        // takes in everything that binds tighter and nothing looser.
+        assert_eq!(stop("== 1 + 2 < 3;"), expected("=="));
+        assert_eq!(stop("+ 3 * 4;"), expected("+"));
+        assert_eq!(stop("< 1 + 2;"), expected("<"));
+        assert_eq!(stop("* 2 ** 3;"), expected("*"));
+    }
+
+    #[test]
+    fn interpolation() {
+        assert_eq!(
+            sexpr("print \"a${x}b${y + 1}\";"),
+            "(print (interpolate \"a\" x \"b\" (+ y 1)))"
+        );
+        assert_eq!(sexpr("print \"${x}\";"), "(print (interpolate x))");
+    }
+
+    #[test]
+    fn nested_interpolation() {
+        assert_eq!(
+            sexpr("print \"${\"${1}\"}\";"),
+            "(print (interpolate (interpolate 1)))"
+        );
+        assert_eq!(
+            sexpr("print \"a${\"b${c}\" + d}\";"),
+            "(print (interpolate \"a\" (+ (interpolate \"b\" c) d)))"
+        );
+    }
+
+    #[test]
+    fn empty_interpolation_is_an_error() {
+        assert_eq!(
+            error("print \"a${}b\";"),
+            (
+                "expected expression inside '${}'".to_string(),
+                Some("}".to_string())
+            )
+        );
+        assert_eq!(
+            error("print \"${\"${}\"}\";").0,
+            "expected expression inside '${}'"
+        );
+    }
+
+    #[test]
+    fn exponents_bind_tighter_than_negation() {
+        assert_eq!(sexpr("-2 ** 2;"), "(expr (- (** 2 2)))");
+        assert_eq!(sexpr("2 ** -1;"), "(expr (** 2 (- 1)))");
+        assert_eq!(sexpr("2 ** 3 ** 2;"), "(expr (** 2 (** 3 2)))");
+    }
+
+    #[test]
+    fn increments_bind_tighter_than_exponents() {
+        assert_eq!(sexpr("++x ** 2;"), "(expr (** (++ x) 2))");
+        assert_eq!(sexpr("x++;"), "(expr (postfix++ x))");
+        assert_eq!(sexpr("x-- ** 2;"), "(expr (** (postfix-- x) 2))");
+    }
+
+    #[test]
+    fn compound_assignments_keep_their_operator() {
+        assert_eq!(sexpr("a += b * c;"), "(expr (+= a (* b c)))");
+        assert_eq!(sexpr("a -= b = c;"), "(expr (-= a (= b c)))");
+    }
+
+    #[test]
+    fn invalid_targets_are_reported() {
+        assert_eq!(error("++(x);").0, "invalid '++' target");
+        assert_eq!(error("1 += 2;").0, "invalid assignment target");
+        assert_eq!(error("a + b = c;").0, "invalid assignment target");
+    }
+
+    #[test]
+    fn statements_with_bodies() {
+        assert_eq!(
+            sexpr("if (a) print 1; else print 2;"),
+            "(if a (print 1) (print 2))"
+        );
+        assert_eq!(
+            sexpr("while (a) { b(); }"),
+            "(while a (block (expr (call b))))"
+        );
+        assert_eq!(
+            sexpr("fun f(a, b) { return a; }"),
+            "(fun f (a b) (block (return a)))"
+        );
+        assert_eq!(sexpr("fun f() { return; }"), "(fun f () (block (return)))");
+    }
+
+    #[test]
+    fn statements_know_their_parent() {
+        let ast = parse("fun f() { if (a) { return; } }").unwrap();
+        let function = ast.statements()[0];
+        assert_eq!(ast.stmt_parent(function), None);
+
+        // Walk down through the body, the `if` and its block to the return.
+        let mut parent = function;
+        for _ in 0..4 {
+            let child = ast.get_stmt(parent).statements()[0];
+            assert_eq!(ast.stmt_parent(child), Some(parent));
+            parent = child;
+        }
+        assert!(matches!(ast.get_stmt(parent), StmtNode::Return(_)));
+    }
+
+    #[test]
+    fn else_belongs_to_the_nearest_if() {
+        assert_eq!(
+            sexpr("if (a) if (b) c; else d;"),
+            "(if a (if b (expr c) (expr d)))"
+        );
+    }
+
+    #[test]
+    fn return_needs_a_function() {
+        assert_eq!(error("return 1;").0, "can't return from top-level code");
+        assert_eq!(error("{ return; }").0, "can't return from top-level code");
+        assert_eq!(
+            error("fun f() {} return;").0,
+            "can't return from top-level code"
+        );
+    }
+
+    #[test]
+    fn malformed_headers() {
+        assert_eq!(error("if a) b;").0, "expected '(' after 'if'");
+        assert_eq!(error("while (a b;").0, "expected ')' after while condition");
+        assert_eq!(error("fun (a) {}").0, "expected function name");
+        assert_eq!(error("fun f(a b) {}").0, "expected ')' after parameters");
+        assert_eq!(
+            error("fun f() print 1;").0,
+            "expected '{' before function body"
+        );
     }
 }
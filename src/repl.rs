@@ -0,0 +1,265 @@
+use crate::error::{IoOperation, RLoxError, RLoxResult};
+use crate::interpreter::{self, Interpreter};
+use crate::repl_config::{self, ReplConfig};
+use crate::repl_helper::ReplHelper;
+use crate::scanner::Scanner;
+use crate::sexpr;
+use crate::token_dump;
+use crate::value::Value;
+
+use rustyline::error::ReadlineError;
+use rustyline::{Config, Editor};
+
+use std::fs;
+use std::path::Path;
+use std::time::Instant;
+
+const HELP: &str = "\
+:tokens <source>  show the tokens scanned from <source>
+:ast <source>     show the syntax tree parsed from <source>
+:load <path>      run a file in the current session
+:env              list global variables and their values
+:reset            forget everything defined in this session
+:time <source>    run <source> and report how long it took
+:help             show this message";
+
+pub fn run_prompt() -> RLoxResult<()> {
+    let config = ReplConfig::load();
+    let mut rl = Editor::<ReplHelper>::with_config(
+        Config::builder()
+            .edit_mode(config.edit_mode)
+            .max_history_size(config.history_size)
+            .build(),
+    );
+    rl.set_helper(Some(ReplHelper {
+        color: config.color,
+        ..ReplHelper::default()
+    }));
+
+    let history = repl_config::state_dir().map(|dir| dir.join("history"));
+    if let Some(history) = &history {
+        // There is no history yet on the first run.
+        let _ = rl.load_history(history);
+    }
+
+    // One interpreter for the whole session, so definitions made on one
+    // line are visible on the next.
+    let mut interpreter = Interpreter::new();
+    if let Some(dir) = repl_config::config_dir() {
+        run_rc_file(&mut interpreter, &dir.join("rloxrc.lox"));
+    }
+
+    let result = read_eval_print(&mut rl, &mut interpreter, &config);
+
+    if let Some(history) = &history {
+        if let Err(err) = save_history(&mut rl, history) {
+            eprintln!("{}", err);
+        }
+    }
+
+    result
+}
+
+fn save_history(rl: &mut Editor<ReplHelper>, path: &Path) -> RLoxResult<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .map_err(|err| RLoxError::io(IoOperation::CreateDir, Some(dir), err))?;
+    }
+    rl.save_history(path).map_err(|err| match err {
+        ReadlineError::Io(err) => RLoxError::io(IoOperation::Write, Some(path), err),
+        err => err.into(),
+    })
+}
+
+/// Runs the startup file, if there is one, so that it can predefine
+/// helpers for the session. Errors in it are reported but do not stop the
+/// REPL from starting.
+fn run_rc_file(interpreter: &mut Interpreter, path: &Path) {
+    let src = match fs::read_to_string(path) {
+        Ok(src) => src,
+        Err(_) => return,
+    };
+    if let Err(err) = interpreter::run(interpreter, src, None, false) {
+        eprintln!("{}: {}", path.display(), err);
+    }
+}
+
+fn read_eval_print(
+    rl: &mut Editor<ReplHelper>,
+    interpreter: &mut Interpreter,
+    config: &ReplConfig,
+) -> RLoxResult<()> {
+    refresh_globals(rl, interpreter);
+    // Lines typed so far for an input that is still incomplete.
+    let mut pending = String::new();
+
+    loop {
+        if let Some(helper) = rl.helper_mut() {
+            helper.pending = pending.clone();
+        }
+
+        let prompt = if pending.is_empty() {
+            &config.prompt
+        } else {
+            &config.continuation_prompt
+        };
+        let line = match rl.readline(prompt) {
+            Err(ReadlineError::Interrupted) if !pending.is_empty() => {
+                pending.clear();
+                continue;
+            }
+            line => line?,
+        };
+
+        if !pending.is_empty() && line.trim().is_empty() {
+            pending.clear();
+            continue;
+        }
+
+        rl.add_history_entry(line.to_string());
+
+        if pending.is_empty() && line.trim_start().starts_with(':') {
+            if let Err(err) = run_command(interpreter, line.trim()) {
+                report(err)?;
+            }
+        } else {
+            pending.push_str(&line);
+            pending.push('\n');
+
+            match run_line(interpreter, pending.clone()) {
+                Err(RLoxError::UnexpectedEOF { .. }) => continue,
+                Err(err) => report(err)?,
+                Ok(_) => (),
+            };
+            pending.clear();
+        }
+
+        refresh_globals(rl, interpreter);
+    }
+}
+
+fn refresh_globals(rl: &mut Editor<ReplHelper>, interpreter: &Interpreter) {
+    if let Some(helper) = rl.helper_mut() {
+        helper.globals = interpreter
+            .globals()
+            .into_iter()
+            .map(|(name, _)| name.to_string())
+            .collect();
+    }
+}
+
+/// Prints an error that should not end the session. Errors that should,
+/// such as the end of input, are handed back to the caller.
+fn report(err: RLoxError) -> RLoxResult<()> {
+    match err {
+        RLoxError::Exit { .. } | RLoxError::Interrupted | RLoxError::EOF => Err(err),
+        err => {
+            eprintln!("{}", err);
+            Ok(())
+        }
+    }
+}
+
+/// Runs one REPL entry and echoes its value when it ends with a bare
+/// expression.
+fn run_line(interpreter: &mut Interpreter, src: String) -> RLoxResult<()> {
+    let ast = interpreter::parse_line(src)?;
+    if let Some(value) = interpreter.interpret(ast)? {
+        println!("{}", value);
+    }
+    Ok(())
+}
+
+/// Runs a `:command`. `line` includes the leading colon.
+fn run_command(interpreter: &mut Interpreter, line: &str) -> RLoxResult<()> {
+    let (command, argument) = match line.find(char::is_whitespace) {
+        Some(at) => (&line[..at], line[at..].trim()),
+        None => (line, ""),
+    };
+
+    match command {
+        ":tokens" => {
+            for token in Scanner::new(argument.to_string()) {
+                println!("{}", token_dump::format_token(&token?));
+            }
+        }
+        ":ast" => {
+            let ast = interpreter::parse_line(argument.to_string())?;
+            println!("{}", sexpr::to_sexpr(&ast));
+        }
+        ":load" => {
+            let src = interpreter::read_source(argument)?;
+            interpreter::run(interpreter, src, None, false)?;
+        }
+        ":env" => {
+            for (name, value) in interpreter.globals() {
+                match value {
+                    Value::String(s) => println!("{} = {:?}", name, s),
+                    value => println!("{} = {}", name, value),
+                }
+            }
+        }
+        ":reset" => *interpreter = Interpreter::new(),
+        ":time" => {
+            let start = Instant::now();
+            let result = run_line(interpreter, argument.to_string());
+            eprintln!("took {:?}", start.elapsed());
+            result?;
+        }
+        ":help" => println!("{}", HELP),
+        _ => eprintln!("unknown command {}, try :help", command),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn incomplete_entries_ask_for_more() {
+        let entries = [
+            "print (1 + 2\n",
+            "print 1 +\n",
+            "print \"abc\n",
+            "{ print 1;\n",
+            "var x =\n",
+            "/* open comment\n",
+            "fun f() {\n",
+            "if (x)\n",
+            "while (x) {\n  print x;\n",
+        ];
+        for entry in &entries {
+            assert!(
+                matches!(
+                    interpreter::parse_line(entry.to_string()),
+                    Err(RLoxError::UnexpectedEOF { .. })
+                ),
+                "{:?} should wait for more input",
+                entry
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_entries_are_errors() {
+        for entry in &["print (1;\n", "print 1 +;\n", "1 = 2;\n", "}\n"] {
+            assert!(
+                matches!(
+                    interpreter::parse_line(entry.to_string()),
+                    Err(RLoxError::Source { .. })
+                ),
+                "{:?} should be rejected",
+                entry
+            );
+        }
+    }
+
+    #[test]
+    fn complete_entries_parse() {
+        for entry in &["1 + 2\n", "print 1;\n", "{\n  print 1;\n}\n"] {
+            assert!(interpreter::parse_line(entry.to_string()).is_ok());
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use rustyline::config::EditMode;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+/// Settings read from `$XDG_CONFIG_HOME/rlox/config`.
+///
+/// The file holds one `key = value` pair per line. Blank lines and lines
+/// starting with `#` are ignored:
+///
+/// ```text
+/// prompt = "lox> "
+/// continuation_prompt = "...> "
+/// color = false
+/// edit_mode = vi
+/// history_size = 500
+/// ```
+#[derive(Debug, Clone)]
+pub struct ReplConfig {
+    pub prompt: String,
+    pub continuation_prompt: String,
+    pub color: bool,
+    pub edit_mode: EditMode,
+    /// Number of entries kept in the history file.
+    pub history_size: usize,
+}
+
+impl Default for ReplConfig {
+    fn default() -> Self {
+        ReplConfig {
+            prompt: "rlox> ".to_string(),
+            continuation_prompt: "...> ".to_string(),
+            color: true,
+            edit_mode: EditMode::Emacs,
+            history_size: 1000,
+        }
+    }
+}
+
+impl ReplConfig {
+    /// Reads the config file, falling back to the defaults for anything it
+    /// does not set. A missing file is not an error; bad lines are reported
+    /// and skipped.
+    pub fn load() -> Self {
+        let mut config = ReplConfig::default();
+        let path = match config_dir() {
+            Some(dir) => dir.join("config"),
+            None => return config,
+        };
+        let src = match fs::read_to_string(&path) {
+            Ok(src) => src,
+            Err(_) => return config,
+        };
+
+        for (index, line) in src.lines().enumerate() {
+            if let Err(message) = config.set_line(line) {
+                eprintln!("{}:{}: {}", path.display(), index + 1, message);
+            }
+        }
+        config
+    }
+
+    fn set_line(&mut self, line: &str) -> Result<(), String> {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(());
+        }
+
+        let (key, value) = match line.find('=') {
+            Some(at) => (line[..at].trim(), unquote(line[at + 1..].trim())),
+            None => return Err(format!("expected 'key = value', found '{}'", line)),
+        };
+
+        match key {
+            "prompt" => self.prompt = value.to_string(),
+            "continuation_prompt" => self.continuation_prompt = value.to_string(),
+            "color" => {
+                self.color = match value {
+                    "true" | "on" => true,
+                    "false" | "off" => false,
+                    _ => return Err(format!("color must be true or false, found '{}'", value)),
+                }
+            }
+            "edit_mode" => {
+                self.edit_mode = match value {
+                    "emacs" => EditMode::Emacs,
+                    "vi" => EditMode::Vi,
+                    _ => return Err(format!("edit_mode must be emacs or vi, found '{}'", value)),
+                }
+            }
+            "history_size" => {
+                self.history_size = value
+                    .parse()
+                    .map_err(|_| format!("history_size must be a number, found '{}'", value))?
+            }
+            _ => return Err(format!("unknown setting '{}'", key)),
+        }
+        Ok(())
+    }
+}
+
+/// Strips one pair of surrounding double quotes, which lets a prompt end in
+/// a space.
+fn unquote(value: &str) -> &str {
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        &value[1..value.len() - 1]
+    } else {
+        value
+    }
+}
+
+/// `$XDG_CONFIG_HOME/rlox`, or `~/.config/rlox` when that is unset.
+pub fn config_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+/// `$XDG_STATE_HOME/rlox`, or `~/.local/state/rlox` when that is unset.
+pub fn state_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_STATE_HOME", ".local/state")
+}
+
+fn xdg_dir(variable: &str, fallback: &str) -> Option<PathBuf> {
+    let base = match env::var_os(variable) {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(fallback),
+    };
+    Some(base.join("rlox"))
+}
@@ -0,0 +1,273 @@
+use crate::scanner::{Scanner, KEYWORDS};
+use crate::token::TokenType;
+
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::{ValidationContext, ValidationResult, Validator};
+use rustyline::{Context, Helper};
+
+use std::borrow::Cow;
+
+const KEYWORD_COLOR: &str = "\x1b[35m";
+const STRING_COLOR: &str = "\x1b[32m";
+const NUMBER_COLOR: &str = "\x1b[33m";
+const OPERATOR_COLOR: &str = "\x1b[36m";
+const COMMENT_COLOR: &str = "\x1b[90m";
+const RESET: &str = "\x1b[0m";
+
+/// Colon-commands understood by the REPL, offered when completing a word
+/// that starts with `:`.
+pub const COMMANDS: [&str; 7] = [
+    ":ast", ":env", ":help", ":load", ":reset", ":time", ":tokens",
+];
+
+/// Line-editing support for the REPL.
+#[derive(Default)]
+pub struct ReplHelper {
+    /// Names bound in the session's global scope. The REPL refreshes this
+    /// after every entry, since the helper cannot reach the interpreter.
+    pub globals: Vec<String>,
+    /// Earlier lines of an entry that is still incomplete, so brackets
+    /// opened on those lines can be matched on this one.
+    pub pending: String,
+    /// Whether to color the input line.
+    pub color: bool,
+}
+
+impl ReplHelper {
+    fn candidates(&self, prefix: &str) -> Vec<String> {
+        let mut candidates: Vec<String> = if prefix.starts_with(':') {
+            COMMANDS.iter().map(|c| c.to_string()).collect()
+        } else {
+            KEYWORDS
+                .iter()
+                .map(|k| k.to_string())
+                .chain(self.globals.iter().cloned())
+                .collect()
+        };
+        candidates.retain(|c| c.starts_with(prefix));
+        candidates.sort();
+        candidates.dedup();
+        candidates
+    }
+}
+
+impl Completer for ReplHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let before = &line[..pos];
+        let start = before
+            .char_indices()
+            .rev()
+            .take_while(|&(_, c)| c.is_alphanumeric() || c == '_')
+            .last()
+            .map_or(pos, |(at, _)| at);
+
+        // A command name at the very start of the line.
+        if before.starts_with(':') && !before.contains(char::is_whitespace) {
+            return Ok((0, self.candidates(before)));
+        }
+
+        // Property access. There are no classes yet, so nothing is known
+        // about the receiver.
+        if before[..start].ends_with('.') {
+            return Ok((start, vec![]));
+        }
+
+        Ok((start, self.candidates(&before[start..])))
+    }
+}
+
+impl Hinter for ReplHelper {}
+
+impl Highlighter for ReplHelper {
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        if self.color {
+            Cow::Owned(highlight(line))
+        } else {
+            Cow::Borrowed(line)
+        }
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize) -> bool {
+        self.color
+    }
+}
+
+impl Validator for ReplHelper {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        let input = ctx.input();
+        if self.pending.is_empty() && input.trim_start().starts_with(':') {
+            return Ok(ValidationResult::Valid(None));
+        }
+
+        let src = format!("{}{}", self.pending, input);
+        Ok(match unmatched_bracket(&src) {
+            Some(message) => ValidationResult::Invalid(Some(format!("  <- {}", message))),
+            None => ValidationResult::Valid(None),
+        })
+    }
+}
+
+impl Helper for ReplHelper {}
+
+fn color(token_type: &TokenType) -> Option<&'static str> {
+    match token_type {
+        TokenType::String(_) | TokenType::Interpolation(_) => Some(STRING_COLOR),
+        TokenType::Number(_) => Some(NUMBER_COLOR),
+        TokenType::Minus
+        | TokenType::Plus
+        | TokenType::Slash
+        | TokenType::Star
+        | TokenType::Percent
+        | TokenType::Question
+        | TokenType::Colon
+        | TokenType::Bang
+        | TokenType::BangEqual
+        | TokenType::Equal
+        | TokenType::EqualEqual
+        | TokenType::Greater
+        | TokenType::GreaterEqual
+        | TokenType::Less
+        | TokenType::LessEqual
+        | TokenType::PlusEqual
+        | TokenType::MinusEqual
+        | TokenType::StarEqual
+        | TokenType::SlashEqual
+        | TokenType::PercentEqual
+        | TokenType::PlusPlus
+        | TokenType::MinusMinus
+        | TokenType::StarStar => Some(OPERATOR_COLOR),
+        TokenType::LeftParen
+        | TokenType::RightParen
+        | TokenType::LeftBrace
+        | TokenType::RightBrace
+        | TokenType::Comma
+        | TokenType::Dot
+        | TokenType::Semicolon
+        | TokenType::Identifier(_)
+        | TokenType::EOF => None,
+        _ => Some(KEYWORD_COLOR),
+    }
+}
+
+fn push_colored(out: &mut String, color: &str, text: &str) {
+    out.push_str(color);
+    out.push_str(text);
+    out.push_str(RESET);
+}
+
+/// Adds ANSI colors to `line`. The line may be partial or invalid: text the
+/// scanner cannot turn into tokens is left as it is.
+fn highlight(line: &str) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    let mut out = String::with_capacity(line.len());
+    let mut last = 0;
+
+    for token in Scanner::new(line.to_string()).flatten() {
+        if token.token_type == TokenType::EOF {
+            break;
+        }
+
+        let (start, end) = (token.span.start.offset, token.span.end.offset);
+        push_gap(&mut out, &chars[last..start]);
+        let text: String = chars[start..end].iter().collect();
+        match color(&token.token_type) {
+            Some(color) => push_colored(&mut out, color, &text),
+            None => out.push_str(&text),
+        }
+        last = end;
+    }
+
+    push_gap(&mut out, &chars[last..]);
+    out
+}
+
+/// Adds text that lies between tokens. That is whitespace and comments,
+/// plus anything the scanner rejected, such as an unterminated string.
+fn push_gap(out: &mut String, gap: &[char]) {
+    let gap: String = gap.iter().collect();
+    let indent = gap.len() - gap.trim_start().len();
+    let (space, rest) = gap.split_at(indent);
+    out.push_str(space);
+
+    if rest.starts_with('"') {
+        push_colored(out, STRING_COLOR, rest);
+        return;
+    }
+
+    let mut rest = rest;
+    while let Some(at) = rest.find('/') {
+        let comment = &rest[at..];
+        let len = if comment.starts_with("//") {
+            comment.find('\n').unwrap_or(comment.len())
+        } else if comment.starts_with("/*") {
+            block_comment_len(comment)
+        } else {
+            out.push_str(&rest[..=at]);
+            rest = &rest[at + 1..];
+            continue;
+        };
+        out.push_str(&rest[..at]);
+        push_colored(out, COMMENT_COLOR, &comment[..len]);
+        rest = &comment[len..];
+    }
+    out.push_str(rest);
+}
+
+/// Length in bytes of the block comment at the start of `text`, counting
+/// nested comments. Runs to the end of `text` if the comment is not closed.
+fn block_comment_len(text: &str) -> usize {
+    let mut depth = 0;
+    let mut at = 0;
+    while at < text.len() {
+        if text[at..].starts_with("/*") {
+            depth += 1;
+            at += 2;
+        } else if text[at..].starts_with("*/") {
+            depth -= 1;
+            at += 2;
+            if depth == 0 {
+                return at;
+            }
+        } else {
+            at += text[at..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+    text.len()
+}
+
+/// Finds the first closing bracket in `src` that does not match the
+/// innermost open one. Brackets left open are fine: the REPL asks for more
+/// input in that case.
+fn unmatched_bracket(src: &str) -> Option<String> {
+    let mut open = Vec::new();
+
+    for token in Scanner::new(src.to_string()).flatten() {
+        let expected = match token.token_type {
+            TokenType::LeftParen | TokenType::LeftBrace => {
+                open.push(token.token_type);
+                continue;
+            }
+            TokenType::RightParen => TokenType::LeftParen,
+            TokenType::RightBrace => TokenType::LeftBrace,
+            _ => continue,
+        };
+
+        if open.pop() != Some(expected) {
+            return Some(format!(
+                "unmatched '{}' at {}",
+                token.token_type, token.span.start
+            ));
+        }
+    }
+
+    None
+}
@@ -1,46 +1,125 @@
 use crate::error::{RLoxError, RLoxResult};
-use crate::token::{Token, TokenType};
+use crate::token::{Position, Span, Token, TokenType};
+
+/// Reserved words, as recognized by `Scanner::parse_identifier`.
+pub const KEYWORDS: [&str; 16] = [
+    "and", "class", "else", "false", "for", "fun", "if", "nil", "or", "print", "return", "super",
+    "this", "true", "var", "while",
+];
 
 pub struct Scanner {
-    src: String,
+    src: Vec<char>,
     lexeme_start: usize,
     lexeme_current: usize,
     line: usize,
+    line_start: usize,
+    start_position: Position,
+    /// Interpolations whose `${` has been scanned but not their closing
+    /// `}`, innermost last. Each counts the braces opened inside it and
+    /// not yet closed.
+    interpolations: Vec<usize>,
+    lossless: bool,
     is_iter_eof: bool,
 }
 
 impl Scanner {
     pub fn new(src: String) -> Self {
         Self {
-            src,
+            src: src.chars().collect(),
             lexeme_start: 0,
             lexeme_current: 0,
             line: 1,
+            line_start: 0,
+            start_position: Position {
+                line: 1,
+                column: 1,
+                offset: 0,
+            },
+            interpolations: Vec::new(),
+            lossless: false,
             is_iter_eof: false,
         }
     }
 
+    /// Makes the scanner emit whitespace and comments as `Whitespace` and
+    /// `Comment` tokens instead of skipping them, so that the lexemes of
+    /// all tokens put together give back the source exactly.
+    pub fn lossless(mut self) -> Self {
+        self.lossless = true;
+        self
+    }
+
     fn is_at_end(&self) -> bool {
         self.lexeme_current >= self.src.len()
     }
 
     fn scan_token(&mut self) -> RLoxResult<Token> {
         if self.is_at_end() {
-            return Ok(Token::new(TokenType::EOF, None, Some(self.line)));
+            return Ok(Token::new(TokenType::EOF, None, Some(self.line)).with_span(self.span()));
         }
 
         let next_char = self.advance();
         match next_char {
             '(' => self.make_token_result(TokenType::LeftParen),
             ')' => self.make_token_result(TokenType::RightParen),
-            '{' => self.make_token_result(TokenType::LeftBrace),
-            '}' => self.make_token_result(TokenType::RightBrace),
+            '{' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
+                self.make_token_result(TokenType::LeftBrace)
+            }
+            '}' => match self.interpolations.last_mut() {
+                // The end of an interpolated expression. The string it was
+                // embedded in carries on.
+                Some(0) => {
+                    self.interpolations.pop();
+                    self.parse_string()
+                }
+                Some(depth) => {
+                    *depth -= 1;
+                    self.make_token_result(TokenType::RightBrace)
+                }
+                None => self.make_token_result(TokenType::RightBrace),
+            },
             ',' => self.make_token_result(TokenType::Comma),
             '.' => self.make_token_result(TokenType::Dot),
-            '-' => self.make_token_result(TokenType::Minus),
-            '+' => self.make_token_result(TokenType::Plus),
+            '-' => {
+                if self.advance_if_match('-') {
+                    self.make_token_result(TokenType::MinusMinus)
+                } else if self.advance_if_match('=') {
+                    self.make_token_result(TokenType::MinusEqual)
+                } else {
+                    self.make_token_result(TokenType::Minus)
+                }
+            }
+            '+' => {
+                if self.advance_if_match('+') {
+                    self.make_token_result(TokenType::PlusPlus)
+                } else if self.advance_if_match('=') {
+                    self.make_token_result(TokenType::PlusEqual)
+                } else {
+                    self.make_token_result(TokenType::Plus)
+                }
+            }
             ';' => self.make_token_result(TokenType::Semicolon),
-            '*' => self.make_token_result(TokenType::Star),
+            '*' => {
+                if self.advance_if_match('*') {
+                    self.make_token_result(TokenType::StarStar)
+                } else if self.advance_if_match('=') {
+                    self.make_token_result(TokenType::StarEqual)
+                } else {
+                    self.make_token_result(TokenType::Star)
+                }
+            }
+            '%' => {
+                if self.advance_if_match('=') {
+                    self.make_token_result(TokenType::PercentEqual)
+                } else {
+                    self.make_token_result(TokenType::Percent)
+                }
+            }
+            '?' => self.make_token_result(TokenType::Question),
+            ':' => self.make_token_result(TokenType::Colon),
             '!' => {
                 if self.advance_if_match('=') {
                     self.make_token_result(TokenType::BangEqual)
@@ -71,26 +150,34 @@ impl Scanner {
             }
             '/' => {
                 if self.advance_if_match('/') {
-                    while self.peek() != '\n' && !self.is_at_end() {
-                        self.advance();
-                    }
-                    self.scan_token()
+                    self.line_comment()
+                } else if self.advance_if_match('*') {
+                    self.block_comment()?;
+                    self.trivia(TokenType::Comment)
+                } else if self.advance_if_match('=') {
+                    self.make_token_result(TokenType::SlashEqual)
                 } else {
                     self.make_token_result(TokenType::Slash)
                 }
             }
-            ' ' | '\r' | '\t' => {
-                self.collapse_scan();
-                self.scan_token()
-            }
-            '\n' => {
-                self.line = self.line + 1;
-                self.collapse_scan();
-                self.scan_token()
+            // A `#!` line at the very start of the source, so scripts can be
+            // made executable. The newline is left in place to keep line
+            // numbers right.
+            '#' if self.lexeme_start == 0 && self.peek() == '!' => self.line_comment(),
+            ' ' | '\r' | '\t' | '\n' => {
+                if next_char == '\n' {
+                    self.new_line();
+                }
+                while matches!(self.peek(), ' ' | '\r' | '\t' | '\n') {
+                    if self.advance() == '\n' {
+                        self.new_line();
+                    }
+                }
+                self.trivia(TokenType::Whitespace)
             }
             '"' => self.parse_string(),
             _ => {
-                if next_char.is_digit(10) {
+                if next_char.is_ascii_digit() {
                     self.parse_number()
                 } else if next_char.is_alphabetic() {
                     self.parse_identifier()
@@ -110,7 +197,7 @@ impl Scanner {
             self.advance();
         }
 
-        let lexeme = self.src[self.lexeme_start..self.lexeme_current].to_string();
+        let lexeme = self.lexeme();
 
         let token = match lexeme.as_str() {
             "and" => TokenType::And,
@@ -132,66 +219,268 @@ impl Scanner {
             _ => TokenType::Identifier(lexeme.clone()),
         };
 
-        Ok(Token::new(token, Some(lexeme), Some(self.line)))
+        Ok(Token::new(token, Some(lexeme), Some(self.line)).with_span(self.span()))
     }
 
+    /// Skips the rest of a `//` comment, up to the end of the line.
+    fn line_comment(&mut self) -> RLoxResult<Token> {
+        while self.peek() != '\n' && !self.is_at_end() {
+            self.advance();
+        }
+        self.trivia(TokenType::Comment)
+    }
+
+    /// Finishes a run of whitespace or a comment: returns it as a token in
+    /// lossless mode and moves on to the next token otherwise.
+    fn trivia(&mut self, token_type: TokenType) -> RLoxResult<Token> {
+        if self.lossless {
+            return self.make_token_result(token_type);
+        }
+        self.collapse_scan();
+        self.scan_token()
+    }
+
+    /// Skips a `/* ... */` comment after its opening delimiter. Comments
+    /// nest, so a region that already holds one can be commented out.
+    fn block_comment(&mut self) -> RLoxResult<()> {
+        let opening = self.start_position;
+        let mut depth = 1;
+
+        while depth > 0 {
+            if self.is_at_end() {
+                return Err(RLoxError::UnexpectedEOF {
+                    line: Some(opening.line),
+                    message: format!("unterminated block comment opened at {}", opening),
+                });
+            }
+
+            match self.advance() {
+                '/' if self.advance_if_match('*') => depth += 1,
+                '*' if self.advance_if_match('/') => depth -= 1,
+                '\n' => self.new_line(),
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Scans a string literal after its opening quote, or the rest of an
+    /// interpolated string after the `}` closing an embedded expression.
+    /// The token's value has escape sequences decoded; its lexeme is the
+    /// source text, delimiters included.
+    ///
+    /// A `${` ends the token early with an `Interpolation` piece, and the
+    /// embedded expression is scanned as ordinary tokens.
     fn parse_string(&mut self) -> RLoxResult<Token> {
+        let mut value = String::new();
+
         // Keep advancing until we read a closing quote or reach the end
         // of file.
         while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line = self.line + 1;
+            let backslash = self.current_position();
+            match self.advance() {
+                '\\' => {
+                    if let Some(c) = self.escape(backslash)? {
+                        value.push(c);
+                    }
+                }
+                '\n' => {
+                    self.new_line();
+                    value.push('\n');
+                }
+                '$' if self.advance_if_match('{') => {
+                    self.interpolations.push(0);
+                    return self.make_token_result(TokenType::Interpolation(value));
+                }
+                c => value.push(c),
             }
-            self.advance();
         }
 
         // If we've read everything and we're now at the end of the file,
         // there was an unterminated string somewhere.
         if self.is_at_end() {
-            return Err(RLoxError::source(
-                Some(self.line),
-                None,
-                "unterminated string".to_string(),
-            ));
+            return Err(RLoxError::UnexpectedEOF {
+                line: Some(self.line),
+                message: "unterminated string".to_string(),
+            });
         }
 
         // The closing quote.
         self.advance();
 
-        // The arithmetic here is for exclusing the starting/ending quotes.
-        let collected_string = self.src[self.lexeme_start + 1..self.lexeme_current - 1].to_string();
-        Ok(Token::new(
-            TokenType::String(collected_string.clone()),
-            Some(collected_string),
-            Some(self.line),
-        ))
+        self.make_token_result(TokenType::String(value))
+    }
+
+    /// Decodes the escape sequence following the backslash at `backslash`.
+    /// Returns `None` when the source ends inside it, which leaves the
+    /// string unterminated.
+    fn escape(&mut self, backslash: Position) -> RLoxResult<Option<char>> {
+        if self.is_at_end() {
+            return Ok(None);
+        }
+
+        let c = match self.advance() {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' => '\\',
+            '"' => '"',
+            '$' => '$',
+            'u' => return self.unicode_escape(backslash).map(Some),
+            other => {
+                return Err(
+                    self.escape_error(backslash, format!("invalid escape sequence '\\{}'", other))
+                )
+            }
+        };
+        Ok(Some(c))
+    }
+
+    /// Decodes the `{XXXX}` part of a `\u{XXXX}` escape: one to six hex
+    /// digits naming a Unicode scalar value.
+    fn unicode_escape(&mut self, backslash: Position) -> RLoxResult<char> {
+        let message =
+            "invalid unicode escape, expected '\\u{' followed by 1 to 6 hex digits and '}'";
+        if !self.advance_if_match('{') {
+            return Err(self.escape_error(backslash, message.to_string()));
+        }
+
+        let mut digits = String::new();
+        while self.peek().is_ascii_hexdigit() && digits.len() < 6 {
+            digits.push(self.advance());
+        }
+        if digits.is_empty() || !self.advance_if_match('}') {
+            return Err(self.escape_error(backslash, message.to_string()));
+        }
+
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(std::char::from_u32)
+            .ok_or_else(|| {
+                self.escape_error(
+                    backslash,
+                    format!("'\\u{{{}}}' is not a valid unicode character", digits),
+                )
+            })
     }
 
+    fn escape_error(&self, backslash: Position, message: String) -> RLoxError {
+        RLoxError::source(
+            Some(backslash.line),
+            Some(format!("at {}", backslash)),
+            message,
+        )
+    }
+
+    /// Scans a number literal after its first digit.
+    ///
+    /// Decimal literals may have a fraction and an exponent, as in
+    /// `1.5e-3`. Integers may instead use a `0x`, `0b` or `0o` prefix. Any
+    /// run of digits may be split with single `_` separators, as in
+    /// `1_000_000`.
     fn parse_number(&mut self) -> RLoxResult<Token> {
-        while self.peek().is_digit(10) {
+        let radix = match (self.src[self.lexeme_start], self.peek()) {
+            ('0', 'x') => Some(16),
+            ('0', 'b') => Some(2),
+            ('0', 'o') => Some(8),
+            _ => None,
+        };
+        let value = match radix {
+            Some(radix) => {
+                self.advance();
+                self.radix_number(radix)?
+            }
+            None => self.decimal_number()?,
+        };
+
+        self.make_token_result(TokenType::Number(value))
+    }
+
+    fn radix_number(&mut self, radix: u32) -> RLoxResult<f64> {
+        while self.peek().is_alphanumeric() || self.peek() == '_' {
             self.advance();
         }
 
-        if self.peek() == '.' && self.peek_next().is_digit(10) {
+        let lexeme = self.lexeme();
+        let (prefix, digits) = lexeme.split_at(2);
+        if digits.is_empty() {
+            return Err(self.number_error(format!("expected digits after '{}'", prefix)));
+        }
+        if let Some(c) = digits.chars().find(|&c| c != '_' && !c.is_digit(radix)) {
+            let kind = match radix {
+                16 => "hexadecimal",
+                8 => "octal",
+                _ => "binary",
+            };
+            return Err(self.number_error(format!("invalid digit '{}' in {} number", c, kind)));
+        }
+        self.check_separators(digits)?;
+
+        u64::from_str_radix(&digits.replace('_', ""), radix)
+            .map(|n| n as f64)
+            .map_err(|_| self.number_error("number is too large".to_string()))
+    }
+
+    fn decimal_number(&mut self) -> RLoxResult<f64> {
+        self.digits();
+
+        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
             self.advance();
+            self.digits();
+        }
 
-            while self.peek().is_digit(10) {
+        if self.peek() == 'e' || self.peek() == 'E' {
+            self.advance();
+            if self.peek() == '+' || self.peek() == '-' {
                 self.advance();
             }
+            if !self.digits() {
+                return Err(self.number_error("expected digits in exponent".to_string()));
+            }
         }
 
-        let lexeme = self.src[self.lexeme_start..self.lexeme_current].to_string();
-        let parsed_number: f64 = lexeme.parse().unwrap();
-        Ok(Token::new(
-            TokenType::Number(parsed_number),
-            Some(lexeme),
-            Some(self.line),
-        ))
+        let lexeme = self.lexeme();
+        for part in lexeme.split(['.', 'e', 'E']) {
+            self.check_separators(part.trim_start_matches(['+', '-']))?;
+        }
+
+        lexeme
+            .replace('_', "")
+            .parse()
+            .map_err(|_| self.number_error("invalid number".to_string()))
+    }
+
+    /// Consumes a run of decimal digits and separators. Returns whether
+    /// there was one.
+    fn digits(&mut self) -> bool {
+        let start = self.lexeme_current;
+        while self.peek().is_ascii_digit() || self.peek() == '_' {
+            self.advance();
+        }
+        self.lexeme_current > start
+    }
+
+    /// Rejects separators that do not sit between two digits, such as in
+    /// `1__0`, `1_` or `0x_1`.
+    fn check_separators(&self, digits: &str) -> RLoxResult<()> {
+        if digits.starts_with('_') || digits.ends_with('_') || digits.contains("__") {
+            return Err(self.number_error("'_' must separate two digits".to_string()));
+        }
+        Ok(())
+    }
+
+    fn number_error(&self, message: String) -> RLoxError {
+        RLoxError::source(Some(self.line), Some(self.lexeme()), message)
     }
 
     fn advance(&mut self) -> char {
-        self.lexeme_current = self.lexeme_current + 1;
-        self.src.chars().nth(self.lexeme_current - 1).unwrap()
+        let next = self.peek();
+        if !self.is_at_end() {
+            self.lexeme_current += 1;
+        }
+        next
     }
 
     fn advance_if_match(&mut self, test: char) -> bool {
@@ -199,11 +488,11 @@ impl Scanner {
             return false;
         }
 
-        if self.src.chars().nth(self.lexeme_current).unwrap() != test {
+        if self.src[self.lexeme_current] != test {
             return false;
         }
 
-        self.lexeme_current = self.lexeme_current + 1;
+        self.lexeme_current += 1;
         true
     }
 
@@ -211,7 +500,7 @@ impl Scanner {
         if self.is_at_end() {
             '\0'
         } else {
-            self.src.chars().nth(self.lexeme_current).unwrap()
+            self.src[self.lexeme_current]
         }
     }
 
@@ -219,16 +508,45 @@ impl Scanner {
         if self.lexeme_current + 1 >= self.src.len() {
             '\0'
         } else {
-            self.src.chars().nth(self.lexeme_current + 1).unwrap()
+            self.src[self.lexeme_current + 1]
         }
     }
 
     fn make_token_result(&mut self, token_type: TokenType) -> RLoxResult<Token> {
-        Ok(Token::new(token_type, None, Some(self.line)))
+        let lexeme = self.lexeme();
+        Ok(Token::new(token_type, Some(lexeme), Some(self.line)).with_span(self.span()))
+    }
+
+    /// The source text from the start of the current lexeme to the current
+    /// position.
+    fn lexeme(&self) -> String {
+        self.src[self.lexeme_start..self.lexeme_current]
+            .iter()
+            .collect()
+    }
+
+    /// Records that the character just consumed was a line break.
+    fn new_line(&mut self) {
+        self.line += 1;
+        self.line_start = self.lexeme_current;
+    }
+
+    fn current_position(&self) -> Position {
+        Position {
+            line: self.line,
+            column: self.lexeme_current - self.line_start + 1,
+            offset: self.lexeme_current,
+        }
+    }
+
+    /// The span from the start of the current lexeme to the current position.
+    fn span(&self) -> Span {
+        Span::new(self.start_position, self.current_position())
     }
 
     fn collapse_scan(&mut self) {
         self.lexeme_start = self.lexeme_current;
+        self.start_position = self.current_position();
     }
 }
 
@@ -240,13 +558,9 @@ impl Iterator for Scanner {
             self.collapse_scan();
             let result = self.scan_token();
 
-            match &result {
-                Ok(token) => match token.token_type {
-                    TokenType::EOF => self.is_iter_eof = true,
-                    _ => (),
-                },
-                Err(_) => (),
-            };
+            if matches!(&result, Ok(token) if token.token_type == TokenType::EOF) {
+                self.is_iter_eof = true;
+            }
 
             Some(result)
         } else {
@@ -254,3 +568,163 @@ impl Iterator for Scanner {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token_types(src: &str) -> RLoxResult<Vec<TokenType>> {
+        Scanner::new(src.to_string())
+            .map(|token| token.map(|t| t.token_type))
+            .collect()
+    }
+
+    fn number(src: &str) -> f64 {
+        match token_types(src).unwrap().as_slice() {
+            [TokenType::Number(n), TokenType::EOF] => *n,
+            other => panic!("expected a single number, got {:?}", other),
+        }
+    }
+
+    fn string(src: &str) -> String {
+        match token_types(src).unwrap().as_slice() {
+            [TokenType::String(s), TokenType::EOF] => s.clone(),
+            other => panic!("expected a single string, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn numbers_in_every_radix() {
+        assert_eq!(number("0x1F"), 31.0);
+        assert_eq!(number("0b101"), 5.0);
+        assert_eq!(number("0o17"), 15.0);
+        assert_eq!(number("12.5"), 12.5);
+    }
+
+    #[test]
+    fn numbers_with_exponents_and_separators() {
+        assert_eq!(number("1.5e3"), 1500.0);
+        assert_eq!(number("25E-2"), 0.25);
+        assert_eq!(number("1_000_000"), 1_000_000.0);
+        assert_eq!(number("0xff_ff"), 65535.0);
+    }
+
+    #[test]
+    fn malformed_numbers_are_rejected() {
+        for src in &["0x", "0b", "1e", "1e+", "1__0", "1_", "0x_1"] {
+            assert!(
+                matches!(token_types(src), Err(RLoxError::Source { .. })),
+                "{} was accepted",
+                src
+            );
+        }
+    }
+
+    #[test]
+    fn escape_sequences() {
+        assert_eq!(string(r#""a\tb\n""#), "a\tb\n");
+        assert_eq!(string(r#""\"\\\$""#), "\"\\$");
+        assert_eq!(string(r#""\u{41}\u{1F600}""#), "A\u{1F600}");
+        assert!(token_types(r#""\q""#).is_err());
+        assert!(token_types(r#""\u{}""#).is_err());
+        assert!(token_types(r#""\u{110000}""#).is_err());
+    }
+
+    /// The line and context of the error scanning `src` stops at.
+    fn error_at(src: &str) -> (Option<usize>, Option<String>) {
+        match token_types(src) {
+            Err(RLoxError::Source { line, context, .. }) => (line, context),
+            other => panic!("expected a source error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn invalid_escapes_point_at_the_backslash() {
+        assert_eq!(
+            error_at("var s = \"ok\";\nprint \"ab\\qc\";"),
+            (Some(2), Some("at 2:10".to_string()))
+        );
+        assert_eq!(
+            error_at("\"\\u{41}\\u{zz}\""),
+            (Some(1), Some("at 1:8".to_string()))
+        );
+        // The column counts characters, not bytes, and restarts on each
+        // line of a multi-line string.
+        assert_eq!(
+            error_at("\"é\n  \\x\""),
+            (Some(2), Some("at 2:3".to_string()))
+        );
+    }
+
+    #[test]
+    fn block_comments_nest() {
+        assert_eq!(
+            token_types("/* a /* b */ c */ 1").unwrap(),
+            vec![TokenType::Number(1.0), TokenType::EOF]
+        );
+        assert!(matches!(
+            token_types("/* a /* b */"),
+            Err(RLoxError::UnexpectedEOF { .. })
+        ));
+    }
+
+    #[test]
+    fn lines_are_counted_inside_block_comments() {
+        let lines: Vec<Option<usize>> = Scanner::new("1 /* a\n/* b\n*/\n*/ 2\n3".to_string())
+            .map(|token| token.unwrap().line)
+            .collect();
+        assert_eq!(lines, vec![Some(1), Some(4), Some(5), Some(5)]);
+
+        // An unterminated comment is reported where it was opened.
+        assert!(matches!(
+            token_types("1\n  /* a\n /* b */\n"),
+            Err(RLoxError::UnexpectedEOF { line: Some(2), .. })
+        ));
+    }
+
+    #[test]
+    fn interpolation_splits_the_string() {
+        assert_eq!(
+            token_types(r#""a${x}b${1 + 2}c""#).unwrap(),
+            vec![
+                TokenType::Interpolation("a".to_string()),
+                TokenType::Identifier("x".to_string()),
+                TokenType::Interpolation("b".to_string()),
+                TokenType::Number(1.0),
+                TokenType::Plus,
+                TokenType::Number(2.0),
+                TokenType::String("c".to_string()),
+                TokenType::EOF,
+            ]
+        );
+    }
+
+    #[test]
+    fn compound_operators() {
+        assert_eq!(
+            token_types("+= -= *= /= %= ++ -- ** ? :").unwrap(),
+            vec![
+                TokenType::PlusEqual,
+                TokenType::MinusEqual,
+                TokenType::StarEqual,
+                TokenType::SlashEqual,
+                TokenType::PercentEqual,
+                TokenType::PlusPlus,
+                TokenType::MinusMinus,
+                TokenType::StarStar,
+                TokenType::Question,
+                TokenType::Colon,
+                TokenType::EOF,
+            ]
+        );
+    }
+
+    #[test]
+    fn lossless_mode_keeps_trivia() {
+        let lexemes: Vec<String> = Scanner::new("x /* c */ // d\n".to_string())
+            .lossless()
+            .map(|token| token.unwrap().lexeme.unwrap_or_default())
+            .collect();
+        assert_eq!(lexemes.concat(), "x /* c */ // d\n");
+    }
+}
@@ -0,0 +1,163 @@
+use crate::ast::{
+    AssignNode, Ast, BinaryNode, BlockNode, CallNode, ConditionalNode, ExprId, ExpressionNode,
+    FunctionNode, GroupingNode, IfNode, IncrementNode, InterpolationNode, LiteralNode, PrintNode,
+    ReturnNode, StmtId, UnaryNode, VarNode, VariableNode, WhileNode,
+};
+use crate::token::TokenType;
+use crate::visitor::Visitor;
+
+/// Renders `ast` in the parenthesized prefix form used by the book, such as
+/// `(* (- 123) (group 45.67))`. Each top-level statement goes on its own
+/// line.
+pub fn to_sexpr(ast: &Ast) -> String {
+    let mut printer = SexprPrinter { acc: String::new() };
+    let lines: Vec<String> = ast
+        .statements()
+        .iter()
+        .map(|&stmt| {
+            printer.visit_stmt(ast, stmt);
+            std::mem::take(&mut printer.acc)
+        })
+        .collect();
+    lines.join("\n")
+}
+
+struct SexprPrinter {
+    acc: String,
+}
+
+impl SexprPrinter {
+    fn parenthesize(&mut self, ast: &Ast, name: &str, children: &[ExprId]) {
+        self.acc.push('(');
+        self.acc.push_str(name);
+        for &child in children {
+            self.acc.push(' ');
+            self.visit_expr(ast, child);
+        }
+        self.acc.push(')');
+    }
+}
+
+impl Visitor for SexprPrinter {
+    fn visit_block_stmt(&mut self, ast: &Ast, _id: StmtId, node: &BlockNode) {
+        self.acc.push_str("(block");
+        for &stmt in node.statements() {
+            self.acc.push(' ');
+            self.visit_stmt(ast, stmt);
+        }
+        self.acc.push(')');
+    }
+
+    fn visit_expression_stmt(&mut self, ast: &Ast, _id: StmtId, node: &ExpressionNode) {
+        self.parenthesize(ast, "expr", &[node.expression()]);
+    }
+
+    fn visit_function_stmt(&mut self, ast: &Ast, _id: StmtId, node: &FunctionNode) {
+        let params: Vec<String> = node
+            .params()
+            .iter()
+            .map(|param| param.token_type.to_string())
+            .collect();
+        self.acc.push_str(&format!(
+            "(fun {} ({}) ",
+            node.name().token_type,
+            params.join(" ")
+        ));
+        self.visit_stmt(ast, node.body());
+        self.acc.push(')');
+    }
+
+    fn visit_if_stmt(&mut self, ast: &Ast, _id: StmtId, node: &IfNode) {
+        self.acc.push_str("(if ");
+        self.visit_expr(ast, node.condition());
+        self.acc.push(' ');
+        self.visit_stmt(ast, node.then_branch());
+        if let Some(else_branch) = node.else_branch() {
+            self.acc.push(' ');
+            self.visit_stmt(ast, else_branch);
+        }
+        self.acc.push(')');
+    }
+
+    fn visit_print_stmt(&mut self, ast: &Ast, _id: StmtId, node: &PrintNode) {
+        self.parenthesize(ast, "print", &[node.expression()]);
+    }
+
+    fn visit_return_stmt(&mut self, ast: &Ast, _id: StmtId, node: &ReturnNode) {
+        let value: Vec<ExprId> = node.value().into_iter().collect();
+        self.parenthesize(ast, "return", &value);
+    }
+
+    fn visit_var_stmt(&mut self, ast: &Ast, _id: StmtId, node: &VarNode) {
+        let name = format!("var {}", node.name().token_type);
+        let initializer: Vec<ExprId> = node.initializer().into_iter().collect();
+        self.parenthesize(ast, &name, &initializer);
+    }
+
+    fn visit_while_stmt(&mut self, ast: &Ast, _id: StmtId, node: &WhileNode) {
+        self.acc.push_str("(while ");
+        self.visit_expr(ast, node.condition());
+        self.acc.push(' ');
+        self.visit_stmt(ast, node.body());
+        self.acc.push(')');
+    }
+
+    fn visit_assign(&mut self, ast: &Ast, _id: ExprId, node: &AssignNode) {
+        let name = format!("{} {}", node.operator().token_type, node.name().token_type);
+        self.parenthesize(ast, &name, &[node.value()]);
+    }
+
+    fn visit_binary(&mut self, ast: &Ast, _id: ExprId, node: &BinaryNode) {
+        let operator = node.operator().token_type.to_string();
+        self.parenthesize(ast, &operator, &[node.left(), node.right()]);
+    }
+
+    fn visit_call(&mut self, ast: &Ast, _id: ExprId, node: &CallNode) {
+        let mut children = vec![node.callee()];
+        children.extend(node.arguments());
+        self.parenthesize(ast, "call", &children);
+    }
+
+    fn visit_conditional(&mut self, ast: &Ast, _id: ExprId, node: &ConditionalNode) {
+        let children = [node.condition(), node.then_branch(), node.else_branch()];
+        self.parenthesize(ast, "?:", &children);
+    }
+
+    fn visit_grouping(&mut self, ast: &Ast, _id: ExprId, node: &GroupingNode) {
+        self.parenthesize(ast, "group", &[node.child()]);
+    }
+
+    fn visit_increment(&mut self, _ast: &Ast, _id: ExprId, node: &IncrementNode) {
+        let (operator, name) = (&node.operator().token_type, &node.name().token_type);
+        if node.is_prefix() {
+            self.acc.push_str(&format!("({} {})", operator, name));
+        } else {
+            self.acc
+                .push_str(&format!("(postfix{} {})", operator, name));
+        }
+    }
+
+    fn visit_interpolation(&mut self, ast: &Ast, _id: ExprId, node: &InterpolationNode) {
+        self.parenthesize(ast, "interpolate", node.parts());
+    }
+
+    fn visit_literal(&mut self, _ast: &Ast, _id: ExprId, node: &LiteralNode) {
+        match node.value() {
+            TokenType::String(s) => self.acc.push_str(&format!("{:?}", s)),
+            value => self.acc.push_str(&value.to_string()),
+        }
+    }
+
+    fn visit_unary(&mut self, ast: &Ast, _id: ExprId, node: &UnaryNode) {
+        let operator = node.operator().token_type.to_string();
+        self.parenthesize(ast, &operator, &[node.child()]);
+    }
+
+    fn visit_variable(&mut self, _ast: &Ast, _id: ExprId, node: &VariableNode) {
+        self.acc.push_str(&node.name().token_type.to_string());
+    }
+
+    fn visit_error(&mut self, _ast: &Ast, _id: ExprId) {
+        self.acc.push_str("(error)");
+    }
+}
@@ -1,5 +1,6 @@
 use std::fmt::{Display, Formatter};
 
+#[allow(clippy::upper_case_acronyms)]
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenType {
     // Single-character tokens.
@@ -14,6 +15,9 @@ pub enum TokenType {
     Semicolon,
     Slash,
     Star,
+    Percent,
+    Question,
+    Colon,
 
     // One or two character tokens.
     Bang,
@@ -24,10 +28,21 @@ pub enum TokenType {
     GreaterEqual,
     Less,
     LessEqual,
+    PlusEqual,
+    MinusEqual,
+    StarEqual,
+    SlashEqual,
+    PercentEqual,
+    PlusPlus,
+    MinusMinus,
+    StarStar,
 
     // Literals.
     Identifier(String),
     String(String),
+    /// A piece of an interpolated string that ends where an embedded
+    /// expression starts, at `${`. The last piece is a `String`.
+    Interpolation(String),
     Number(f64),
 
     // Keywords.
@@ -48,9 +63,92 @@ pub enum TokenType {
     Var,
     While,
 
+    // Trivia, only produced by a lossless `Scanner`.
+    Whitespace,
+    Comment,
+
     EOF,
 }
 
+impl TokenType {
+    /// The binary operator a compound assignment token such as `+=`
+    /// applies.
+    pub fn compound_operator(&self) -> Option<TokenType> {
+        match self {
+            TokenType::PlusEqual => Some(TokenType::Plus),
+            TokenType::MinusEqual => Some(TokenType::Minus),
+            TokenType::StarEqual => Some(TokenType::Star),
+            TokenType::SlashEqual => Some(TokenType::Slash),
+            TokenType::PercentEqual => Some(TokenType::Percent),
+            _ => None,
+        }
+    }
+
+    /// Whether this is whitespace or a comment.
+    pub fn is_trivia(&self) -> bool {
+        matches!(self, TokenType::Whitespace | TokenType::Comment)
+    }
+
+    /// The name of the variant, without any literal value it carries.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TokenType::LeftParen => "LeftParen",
+            TokenType::RightParen => "RightParen",
+            TokenType::LeftBrace => "LeftBrace",
+            TokenType::RightBrace => "RightBrace",
+            TokenType::Comma => "Comma",
+            TokenType::Dot => "Dot",
+            TokenType::Minus => "Minus",
+            TokenType::Plus => "Plus",
+            TokenType::Semicolon => "Semicolon",
+            TokenType::Slash => "Slash",
+            TokenType::Star => "Star",
+            TokenType::Percent => "Percent",
+            TokenType::Question => "Question",
+            TokenType::Colon => "Colon",
+            TokenType::Bang => "Bang",
+            TokenType::BangEqual => "BangEqual",
+            TokenType::Equal => "Equal",
+            TokenType::EqualEqual => "EqualEqual",
+            TokenType::Greater => "Greater",
+            TokenType::GreaterEqual => "GreaterEqual",
+            TokenType::Less => "Less",
+            TokenType::LessEqual => "LessEqual",
+            TokenType::PlusEqual => "PlusEqual",
+            TokenType::MinusEqual => "MinusEqual",
+            TokenType::StarEqual => "StarEqual",
+            TokenType::SlashEqual => "SlashEqual",
+            TokenType::PercentEqual => "PercentEqual",
+            TokenType::PlusPlus => "PlusPlus",
+            TokenType::MinusMinus => "MinusMinus",
+            TokenType::StarStar => "StarStar",
+            TokenType::Identifier(_) => "Identifier",
+            TokenType::String(_) => "String",
+            TokenType::Interpolation(_) => "Interpolation",
+            TokenType::Number(_) => "Number",
+            TokenType::And => "And",
+            TokenType::Class => "Class",
+            TokenType::Else => "Else",
+            TokenType::False => "False",
+            TokenType::Fun => "Fun",
+            TokenType::For => "For",
+            TokenType::If => "If",
+            TokenType::Nil => "Nil",
+            TokenType::Or => "Or",
+            TokenType::Print => "Print",
+            TokenType::Return => "Return",
+            TokenType::Super => "Super",
+            TokenType::This => "This",
+            TokenType::True => "True",
+            TokenType::Var => "Var",
+            TokenType::While => "While",
+            TokenType::Whitespace => "Whitespace",
+            TokenType::Comment => "Comment",
+            TokenType::EOF => "EOF",
+        }
+    }
+}
+
 impl Display for TokenType {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         let repr = match self {
@@ -65,6 +163,9 @@ impl Display for TokenType {
             TokenType::Semicolon => ";".to_string(),
             TokenType::Slash => "/".to_string(),
             TokenType::Star => "*".to_string(),
+            TokenType::Percent => "%".to_string(),
+            TokenType::Question => "?".to_string(),
+            TokenType::Colon => ":".to_string(),
             TokenType::Bang => "!".to_string(),
             TokenType::BangEqual => "!=".to_string(),
             TokenType::Equal => "=".to_string(),
@@ -73,8 +174,17 @@ impl Display for TokenType {
             TokenType::GreaterEqual => ">=".to_string(),
             TokenType::Less => "<".to_string(),
             TokenType::LessEqual => "<=".to_string(),
+            TokenType::PlusEqual => "+=".to_string(),
+            TokenType::MinusEqual => "-=".to_string(),
+            TokenType::StarEqual => "*=".to_string(),
+            TokenType::SlashEqual => "/=".to_string(),
+            TokenType::PercentEqual => "%=".to_string(),
+            TokenType::PlusPlus => "++".to_string(),
+            TokenType::MinusMinus => "--".to_string(),
+            TokenType::StarStar => "**".to_string(),
             TokenType::Identifier(ident) => ident.to_string(),
             TokenType::String(s) => s.to_string(),
+            TokenType::Interpolation(s) => s.to_string(),
             TokenType::Number(n) => n.to_string().to_string(),
             TokenType::And => "and".to_string(),
             TokenType::Class => "class".to_string(),
@@ -92,17 +202,60 @@ impl Display for TokenType {
             TokenType::True => "true".to_string(),
             TokenType::Var => "var".to_string(),
             TokenType::While => "while".to_string(),
+            TokenType::Whitespace => "whitespace".to_string(),
+            TokenType::Comment => "comment".to_string(),
             TokenType::EOF => "EOF".to_string(),
         };
         write!(f, "{}", repr)
     }
 }
 
+/// A line and column in the source, both starting at 1, along with the
+/// number of characters before it.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+    pub offset: usize,
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+/// The region of source a token or syntax node was read from. `end` points
+/// just past the last character.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    pub fn new(start: Position, end: Position) -> Self {
+        Self { start, end }
+    }
+
+    /// Returns the smallest span covering both `self` and `other`.
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start, other.end)
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Token {
     pub token_type: TokenType,
     pub lexeme: Option<String>,
     pub line: Option<usize>,
+    pub span: Span,
 }
 
 impl Token {
@@ -111,6 +264,12 @@ impl Token {
             token_type,
             lexeme,
             line,
+            span: Span::default(),
         }
     }
+
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = span;
+        self
+    }
 }
@@ -0,0 +1,46 @@
+use crate::ast_json::span_json;
+use crate::json::JsonValue;
+use crate::token::{Token, TokenType};
+
+/// Formats `token` as one line of a token dump: its start position, kind,
+/// lexeme and, for literals, the value the scanner produced.
+pub fn format_token(token: &Token) -> String {
+    let lexeme = format!("{:?}", token.lexeme.as_deref().unwrap_or(""));
+    let line = format!(
+        "{:<8} {:<13} {:<16} {}",
+        token.span.start.to_string(),
+        token.token_type.name(),
+        lexeme,
+        literal_text(&token.token_type)
+    );
+    line.trim_end().to_string()
+}
+
+/// Describes `token` as a JSON object with the same fields as `format_token`
+/// plus the full span.
+pub fn token_json(token: &Token) -> JsonValue {
+    let lexeme = match &token.lexeme {
+        Some(lexeme) => JsonValue::string(lexeme),
+        None => JsonValue::Null,
+    };
+    let literal = match &token.token_type {
+        TokenType::Number(n) => JsonValue::Number(*n),
+        TokenType::String(s) | TokenType::Interpolation(s) => JsonValue::string(s),
+        _ => JsonValue::Null,
+    };
+
+    JsonValue::object(vec![
+        ("kind", JsonValue::string(token.token_type.name())),
+        ("lexeme", lexeme),
+        ("literal", literal),
+        ("span", span_json(token.span)),
+    ])
+}
+
+fn literal_text(token_type: &TokenType) -> String {
+    match token_type {
+        TokenType::Number(n) => n.to_string(),
+        TokenType::String(s) | TokenType::Interpolation(s) => format!("{:?}", s),
+        _ => String::new(),
+    }
+}
@@ -0,0 +1,97 @@
+use crate::ast::{Ast, FunctionNode, StmtId, StmtNode};
+use crate::environment::Environment;
+use crate::error::RLoxResult;
+use crate::interpreter::Interpreter;
+
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
+
+/// Signature of a function implemented in Rust. It receives the arguments
+/// already evaluated and the line of the call, for error messages.
+pub type NativeFn = fn(&Interpreter, &[Value], Option<usize>) -> RLoxResult<Value>;
+
+/// A function built into the interpreter, such as `args`.
+#[derive(Debug)]
+pub struct NativeFunction {
+    pub name: &'static str,
+    pub arity: usize,
+    pub function: NativeFn,
+}
+
+impl PartialEq for NativeFunction {
+    /// Natives are only ever defined once, so the name identifies them.
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+/// A function declared in Lox, along with the variables it closes over.
+#[derive(Debug)]
+pub struct Function {
+    /// The program the declaration is part of, kept alive for as long as
+    /// the function is.
+    pub ast: Rc<Ast>,
+    pub declaration: StmtId,
+    pub closure: Environment,
+}
+
+impl Function {
+    pub fn declaration(&self) -> &FunctionNode {
+        match self.ast.get_stmt(self.declaration) {
+            StmtNode::Function(node) => node,
+            _ => unreachable!("functions are only made from declarations"),
+        }
+    }
+}
+
+impl PartialEq for Function {
+    /// Each evaluation of a declaration makes a new function, so functions
+    /// are only equal to themselves.
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// A Lox value at runtime.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Nil,
+    Boolean(bool),
+    Number(f64),
+    String(String),
+    List(Rc<Vec<Value>>),
+    Native(Rc<NativeFunction>),
+    Function(Rc<Function>),
+}
+
+impl Value {
+    /// Lox treats `nil` and `false` as false and everything else as true.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Nil | Value::Boolean(false))
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Nil => write!(f, "nil"),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::String(s) => write!(f, "{}", s),
+            Value::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Value::Native(native) => write!(f, "<native fn {}>", native.name),
+            Value::Function(function) => {
+                write!(f, "<fn {}>", function.declaration().name().token_type)
+            }
+        }
+    }
+}
@@ -0,0 +1,509 @@
+use crate::ast::{
+    ASTNode, AssignNode, Ast, BinaryNode, BlockNode, CallNode, ConditionalNode, ExprId,
+    ExpressionNode, FunctionNode, GroupingNode, IfNode, IncrementNode, InterpolationNode,
+    LiteralNode, PrintNode, ReturnNode, StmtId, StmtNode, UnaryNode, VarNode, VariableNode,
+    WhileNode,
+};
+
+/// Read-only traversal over an `Ast`.
+///
+/// Every `visit_*` method defaults to walking the node's children, so an
+/// implementation only needs to override the nodes it cares about. Call the
+/// matching `walk_*` function from an override to keep descending.
+pub trait Visitor {
+    /// Visits every top-level statement in source order.
+    fn visit_program(&mut self, ast: &Ast) {
+        for &stmt in ast.statements() {
+            self.visit_stmt(ast, stmt);
+        }
+    }
+
+    fn visit_stmt(&mut self, ast: &Ast, id: StmtId) {
+        walk_stmt(self, ast, id);
+    }
+
+    fn visit_block_stmt(&mut self, ast: &Ast, _id: StmtId, node: &BlockNode) {
+        walk_block_stmt(self, ast, node);
+    }
+
+    fn visit_expression_stmt(&mut self, ast: &Ast, _id: StmtId, node: &ExpressionNode) {
+        walk_expression_stmt(self, ast, node);
+    }
+
+    fn visit_function_stmt(&mut self, ast: &Ast, _id: StmtId, node: &FunctionNode) {
+        walk_function_stmt(self, ast, node);
+    }
+
+    fn visit_if_stmt(&mut self, ast: &Ast, _id: StmtId, node: &IfNode) {
+        walk_if_stmt(self, ast, node);
+    }
+
+    fn visit_print_stmt(&mut self, ast: &Ast, _id: StmtId, node: &PrintNode) {
+        walk_print_stmt(self, ast, node);
+    }
+
+    fn visit_return_stmt(&mut self, ast: &Ast, _id: StmtId, node: &ReturnNode) {
+        walk_return_stmt(self, ast, node);
+    }
+
+    fn visit_var_stmt(&mut self, ast: &Ast, _id: StmtId, node: &VarNode) {
+        walk_var_stmt(self, ast, node);
+    }
+
+    fn visit_while_stmt(&mut self, ast: &Ast, _id: StmtId, node: &WhileNode) {
+        walk_while_stmt(self, ast, node);
+    }
+
+    fn visit_expr(&mut self, ast: &Ast, id: ExprId) {
+        walk_expr(self, ast, id);
+    }
+
+    fn visit_assign(&mut self, ast: &Ast, _id: ExprId, node: &AssignNode) {
+        walk_assign(self, ast, node);
+    }
+
+    fn visit_binary(&mut self, ast: &Ast, _id: ExprId, node: &BinaryNode) {
+        walk_binary(self, ast, node);
+    }
+
+    fn visit_call(&mut self, ast: &Ast, _id: ExprId, node: &CallNode) {
+        walk_call(self, ast, node);
+    }
+
+    fn visit_conditional(&mut self, ast: &Ast, _id: ExprId, node: &ConditionalNode) {
+        walk_conditional(self, ast, node);
+    }
+
+    fn visit_grouping(&mut self, ast: &Ast, _id: ExprId, node: &GroupingNode) {
+        walk_grouping(self, ast, node);
+    }
+
+    fn visit_increment(&mut self, _ast: &Ast, _id: ExprId, _node: &IncrementNode) {}
+
+    fn visit_interpolation(&mut self, ast: &Ast, _id: ExprId, node: &InterpolationNode) {
+        walk_interpolation(self, ast, node);
+    }
+
+    fn visit_literal(&mut self, _ast: &Ast, _id: ExprId, _node: &LiteralNode) {}
+
+    fn visit_unary(&mut self, ast: &Ast, _id: ExprId, node: &UnaryNode) {
+        walk_unary(self, ast, node);
+    }
+
+    fn visit_variable(&mut self, _ast: &Ast, _id: ExprId, _node: &VariableNode) {}
+
+    fn visit_error(&mut self, _ast: &Ast, _id: ExprId) {}
+}
+
+/// Dispatches to the `Visitor` method matching the kind of statement `id`.
+pub fn walk_stmt<V: Visitor + ?Sized>(visitor: &mut V, ast: &Ast, id: StmtId) {
+    match ast.get_stmt(id) {
+        StmtNode::Block(node) => visitor.visit_block_stmt(ast, id, node),
+        StmtNode::Expression(node) => visitor.visit_expression_stmt(ast, id, node),
+        StmtNode::Function(node) => visitor.visit_function_stmt(ast, id, node),
+        StmtNode::If(node) => visitor.visit_if_stmt(ast, id, node),
+        StmtNode::Print(node) => visitor.visit_print_stmt(ast, id, node),
+        StmtNode::Return(node) => visitor.visit_return_stmt(ast, id, node),
+        StmtNode::Var(node) => visitor.visit_var_stmt(ast, id, node),
+        StmtNode::While(node) => visitor.visit_while_stmt(ast, id, node),
+    }
+}
+
+pub fn walk_block_stmt<V: Visitor + ?Sized>(visitor: &mut V, ast: &Ast, node: &BlockNode) {
+    for &stmt in node.statements() {
+        visitor.visit_stmt(ast, stmt);
+    }
+}
+
+pub fn walk_expression_stmt<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    ast: &Ast,
+    node: &ExpressionNode,
+) {
+    visitor.visit_expr(ast, node.expression());
+}
+
+pub fn walk_function_stmt<V: Visitor + ?Sized>(visitor: &mut V, ast: &Ast, node: &FunctionNode) {
+    visitor.visit_stmt(ast, node.body());
+}
+
+pub fn walk_if_stmt<V: Visitor + ?Sized>(visitor: &mut V, ast: &Ast, node: &IfNode) {
+    visitor.visit_expr(ast, node.condition());
+    visitor.visit_stmt(ast, node.then_branch());
+    if let Some(else_branch) = node.else_branch() {
+        visitor.visit_stmt(ast, else_branch);
+    }
+}
+
+pub fn walk_print_stmt<V: Visitor + ?Sized>(visitor: &mut V, ast: &Ast, node: &PrintNode) {
+    visitor.visit_expr(ast, node.expression());
+}
+
+pub fn walk_return_stmt<V: Visitor + ?Sized>(visitor: &mut V, ast: &Ast, node: &ReturnNode) {
+    if let Some(value) = node.value() {
+        visitor.visit_expr(ast, value);
+    }
+}
+
+pub fn walk_var_stmt<V: Visitor + ?Sized>(visitor: &mut V, ast: &Ast, node: &VarNode) {
+    if let Some(initializer) = node.initializer() {
+        visitor.visit_expr(ast, initializer);
+    }
+}
+
+pub fn walk_while_stmt<V: Visitor + ?Sized>(visitor: &mut V, ast: &Ast, node: &WhileNode) {
+    visitor.visit_expr(ast, node.condition());
+    visitor.visit_stmt(ast, node.body());
+}
+
+/// Dispatches to the `Visitor` method matching the kind of node `id`.
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, ast: &Ast, id: ExprId) {
+    match ast.get(id) {
+        ASTNode::Assign(node) => visitor.visit_assign(ast, id, node),
+        ASTNode::Binary(node) => visitor.visit_binary(ast, id, node),
+        ASTNode::Call(node) => visitor.visit_call(ast, id, node),
+        ASTNode::Conditional(node) => visitor.visit_conditional(ast, id, node),
+        ASTNode::Grouping(node) => visitor.visit_grouping(ast, id, node),
+        ASTNode::Increment(node) => visitor.visit_increment(ast, id, node),
+        ASTNode::Interpolation(node) => visitor.visit_interpolation(ast, id, node),
+        ASTNode::Literal(node) => visitor.visit_literal(ast, id, node),
+        ASTNode::Unary(node) => visitor.visit_unary(ast, id, node),
+        ASTNode::Variable(node) => visitor.visit_variable(ast, id, node),
+        ASTNode::Error => visitor.visit_error(ast, id),
+    }
+}
+
+pub fn walk_assign<V: Visitor + ?Sized>(visitor: &mut V, ast: &Ast, node: &AssignNode) {
+    visitor.visit_expr(ast, node.value());
+}
+
+pub fn walk_binary<V: Visitor + ?Sized>(visitor: &mut V, ast: &Ast, node: &BinaryNode) {
+    visitor.visit_expr(ast, node.left());
+    visitor.visit_expr(ast, node.right());
+}
+
+pub fn walk_call<V: Visitor + ?Sized>(visitor: &mut V, ast: &Ast, node: &CallNode) {
+    visitor.visit_expr(ast, node.callee());
+    for &argument in node.arguments() {
+        visitor.visit_expr(ast, argument);
+    }
+}
+
+pub fn walk_conditional<V: Visitor + ?Sized>(visitor: &mut V, ast: &Ast, node: &ConditionalNode) {
+    visitor.visit_expr(ast, node.condition());
+    visitor.visit_expr(ast, node.then_branch());
+    visitor.visit_expr(ast, node.else_branch());
+}
+
+pub fn walk_grouping<V: Visitor + ?Sized>(visitor: &mut V, ast: &Ast, node: &GroupingNode) {
+    visitor.visit_expr(ast, node.child());
+}
+
+pub fn walk_interpolation<V: Visitor + ?Sized>(
+    visitor: &mut V,
+    ast: &Ast,
+    node: &InterpolationNode,
+) {
+    for &part in node.parts() {
+        visitor.visit_expr(ast, part);
+    }
+}
+
+pub fn walk_unary<V: Visitor + ?Sized>(visitor: &mut V, ast: &Ast, node: &UnaryNode) {
+    visitor.visit_expr(ast, node.child());
+}
+
+/// Traversal that computes a value for every node, such as an evaluator.
+///
+/// Unlike `Visitor` there are no default walks, since only the
+/// implementation knows how to combine the values of a node's children.
+/// `visit_stmt` and `visit_expr` dispatch on the kind of node.
+pub trait ValueVisitor {
+    type Output;
+
+    fn visit_stmt(&mut self, ast: &Ast, id: StmtId) -> Self::Output {
+        match ast.get_stmt(id) {
+            StmtNode::Block(node) => self.visit_block_stmt(ast, id, node),
+            StmtNode::Expression(node) => self.visit_expression_stmt(ast, id, node),
+            StmtNode::Function(node) => self.visit_function_stmt(ast, id, node),
+            StmtNode::If(node) => self.visit_if_stmt(ast, id, node),
+            StmtNode::Print(node) => self.visit_print_stmt(ast, id, node),
+            StmtNode::Return(node) => self.visit_return_stmt(ast, id, node),
+            StmtNode::Var(node) => self.visit_var_stmt(ast, id, node),
+            StmtNode::While(node) => self.visit_while_stmt(ast, id, node),
+        }
+    }
+
+    fn visit_block_stmt(&mut self, ast: &Ast, id: StmtId, node: &BlockNode) -> Self::Output;
+
+    fn visit_expression_stmt(
+        &mut self,
+        ast: &Ast,
+        id: StmtId,
+        node: &ExpressionNode,
+    ) -> Self::Output;
+
+    fn visit_function_stmt(&mut self, ast: &Ast, id: StmtId, node: &FunctionNode) -> Self::Output;
+
+    fn visit_if_stmt(&mut self, ast: &Ast, id: StmtId, node: &IfNode) -> Self::Output;
+
+    fn visit_print_stmt(&mut self, ast: &Ast, id: StmtId, node: &PrintNode) -> Self::Output;
+
+    fn visit_return_stmt(&mut self, ast: &Ast, id: StmtId, node: &ReturnNode) -> Self::Output;
+
+    fn visit_var_stmt(&mut self, ast: &Ast, id: StmtId, node: &VarNode) -> Self::Output;
+
+    fn visit_while_stmt(&mut self, ast: &Ast, id: StmtId, node: &WhileNode) -> Self::Output;
+
+    fn visit_expr(&mut self, ast: &Ast, id: ExprId) -> Self::Output {
+        match ast.get(id) {
+            ASTNode::Assign(node) => self.visit_assign(ast, id, node),
+            ASTNode::Binary(node) => self.visit_binary(ast, id, node),
+            ASTNode::Call(node) => self.visit_call(ast, id, node),
+            ASTNode::Conditional(node) => self.visit_conditional(ast, id, node),
+            ASTNode::Grouping(node) => self.visit_grouping(ast, id, node),
+            ASTNode::Increment(node) => self.visit_increment(ast, id, node),
+            ASTNode::Interpolation(node) => self.visit_interpolation(ast, id, node),
+            ASTNode::Literal(node) => self.visit_literal(ast, id, node),
+            ASTNode::Unary(node) => self.visit_unary(ast, id, node),
+            ASTNode::Variable(node) => self.visit_variable(ast, id, node),
+            ASTNode::Error => self.visit_error(ast, id),
+        }
+    }
+
+    fn visit_assign(&mut self, ast: &Ast, id: ExprId, node: &AssignNode) -> Self::Output;
+
+    fn visit_binary(&mut self, ast: &Ast, id: ExprId, node: &BinaryNode) -> Self::Output;
+
+    fn visit_call(&mut self, ast: &Ast, id: ExprId, node: &CallNode) -> Self::Output;
+
+    fn visit_conditional(&mut self, ast: &Ast, id: ExprId, node: &ConditionalNode) -> Self::Output;
+
+    fn visit_grouping(&mut self, ast: &Ast, id: ExprId, node: &GroupingNode) -> Self::Output;
+
+    fn visit_increment(&mut self, ast: &Ast, id: ExprId, node: &IncrementNode) -> Self::Output;
+
+    fn visit_interpolation(
+        &mut self,
+        ast: &Ast,
+        id: ExprId,
+        node: &InterpolationNode,
+    ) -> Self::Output;
+
+    fn visit_literal(&mut self, ast: &Ast, id: ExprId, node: &LiteralNode) -> Self::Output;
+
+    fn visit_unary(&mut self, ast: &Ast, id: ExprId, node: &UnaryNode) -> Self::Output;
+
+    fn visit_variable(&mut self, ast: &Ast, id: ExprId, node: &VariableNode) -> Self::Output;
+
+    fn visit_error(&mut self, ast: &Ast, id: ExprId) -> Self::Output;
+}
+
+/// Mutating traversal over an `Ast`.
+///
+/// Methods receive the node's handle rather than a reference to it, so an
+/// implementation is free to rewrite the node in place with `Ast::replace`.
+/// The default methods visit children before returning, which makes
+/// bottom-up rewrites (such as folding constants) the natural shape.
+pub trait VisitorMut {
+    fn visit_program_mut(&mut self, ast: &mut Ast) {
+        for stmt in ast.statements().to_vec() {
+            self.visit_stmt_mut(ast, stmt);
+        }
+    }
+
+    fn visit_stmt_mut(&mut self, ast: &mut Ast, id: StmtId) {
+        walk_stmt_mut(self, ast, id);
+    }
+
+    fn visit_block_stmt_mut(&mut self, ast: &mut Ast, id: StmtId) {
+        walk_stmt_children_mut(self, ast, id);
+    }
+
+    fn visit_expression_stmt_mut(&mut self, ast: &mut Ast, id: StmtId) {
+        walk_stmt_children_mut(self, ast, id);
+    }
+
+    fn visit_function_stmt_mut(&mut self, ast: &mut Ast, id: StmtId) {
+        walk_stmt_children_mut(self, ast, id);
+    }
+
+    fn visit_if_stmt_mut(&mut self, ast: &mut Ast, id: StmtId) {
+        walk_stmt_children_mut(self, ast, id);
+    }
+
+    fn visit_print_stmt_mut(&mut self, ast: &mut Ast, id: StmtId) {
+        walk_stmt_children_mut(self, ast, id);
+    }
+
+    fn visit_return_stmt_mut(&mut self, ast: &mut Ast, id: StmtId) {
+        walk_stmt_children_mut(self, ast, id);
+    }
+
+    fn visit_var_stmt_mut(&mut self, ast: &mut Ast, id: StmtId) {
+        walk_stmt_children_mut(self, ast, id);
+    }
+
+    fn visit_while_stmt_mut(&mut self, ast: &mut Ast, id: StmtId) {
+        walk_stmt_children_mut(self, ast, id);
+    }
+
+    fn visit_expr_mut(&mut self, ast: &mut Ast, id: ExprId) {
+        walk_expr_mut(self, ast, id);
+    }
+
+    fn visit_assign_mut(&mut self, ast: &mut Ast, id: ExprId) {
+        walk_children_mut(self, ast, id);
+    }
+
+    fn visit_binary_mut(&mut self, ast: &mut Ast, id: ExprId) {
+        walk_children_mut(self, ast, id);
+    }
+
+    fn visit_call_mut(&mut self, ast: &mut Ast, id: ExprId) {
+        walk_children_mut(self, ast, id);
+    }
+
+    fn visit_conditional_mut(&mut self, ast: &mut Ast, id: ExprId) {
+        walk_children_mut(self, ast, id);
+    }
+
+    fn visit_grouping_mut(&mut self, ast: &mut Ast, id: ExprId) {
+        walk_children_mut(self, ast, id);
+    }
+
+    fn visit_increment_mut(&mut self, _ast: &mut Ast, _id: ExprId) {}
+
+    fn visit_interpolation_mut(&mut self, ast: &mut Ast, id: ExprId) {
+        walk_children_mut(self, ast, id);
+    }
+
+    fn visit_literal_mut(&mut self, _ast: &mut Ast, _id: ExprId) {}
+
+    fn visit_unary_mut(&mut self, ast: &mut Ast, id: ExprId) {
+        walk_children_mut(self, ast, id);
+    }
+
+    fn visit_variable_mut(&mut self, _ast: &mut Ast, _id: ExprId) {}
+
+    fn visit_error_mut(&mut self, _ast: &mut Ast, _id: ExprId) {}
+}
+
+/// Dispatches to the `VisitorMut` method matching the kind of statement
+/// `id`.
+pub fn walk_stmt_mut<V: VisitorMut + ?Sized>(visitor: &mut V, ast: &mut Ast, id: StmtId) {
+    match ast.get_stmt(id) {
+        StmtNode::Block(_) => visitor.visit_block_stmt_mut(ast, id),
+        StmtNode::Expression(_) => visitor.visit_expression_stmt_mut(ast, id),
+        StmtNode::Function(_) => visitor.visit_function_stmt_mut(ast, id),
+        StmtNode::If(_) => visitor.visit_if_stmt_mut(ast, id),
+        StmtNode::Print(_) => visitor.visit_print_stmt_mut(ast, id),
+        StmtNode::Return(_) => visitor.visit_return_stmt_mut(ast, id),
+        StmtNode::Var(_) => visitor.visit_var_stmt_mut(ast, id),
+        StmtNode::While(_) => visitor.visit_while_stmt_mut(ast, id),
+    }
+}
+
+/// Visits the expressions held by statement `id`, then its nested
+/// statements.
+pub fn walk_stmt_children_mut<V: VisitorMut + ?Sized>(visitor: &mut V, ast: &mut Ast, id: StmtId) {
+    let node = ast.get_stmt(id);
+    let (expressions, statements) = (node.expressions(), node.statements());
+    for expr in expressions {
+        visitor.visit_expr_mut(ast, expr);
+    }
+    for stmt in statements {
+        visitor.visit_stmt_mut(ast, stmt);
+    }
+}
+
+/// Dispatches to the `VisitorMut` method matching the kind of node `id`.
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, ast: &mut Ast, id: ExprId) {
+    match ast.get(id) {
+        ASTNode::Assign(_) => visitor.visit_assign_mut(ast, id),
+        ASTNode::Binary(_) => visitor.visit_binary_mut(ast, id),
+        ASTNode::Call(_) => visitor.visit_call_mut(ast, id),
+        ASTNode::Conditional(_) => visitor.visit_conditional_mut(ast, id),
+        ASTNode::Grouping(_) => visitor.visit_grouping_mut(ast, id),
+        ASTNode::Increment(_) => visitor.visit_increment_mut(ast, id),
+        ASTNode::Interpolation(_) => visitor.visit_interpolation_mut(ast, id),
+        ASTNode::Literal(_) => visitor.visit_literal_mut(ast, id),
+        ASTNode::Unary(_) => visitor.visit_unary_mut(ast, id),
+        ASTNode::Variable(_) => visitor.visit_variable_mut(ast, id),
+        ASTNode::Error => visitor.visit_error_mut(ast, id),
+    }
+}
+
+/// Visits every direct child of `id`, left to right.
+pub fn walk_children_mut<V: VisitorMut + ?Sized>(visitor: &mut V, ast: &mut Ast, id: ExprId) {
+    for child in ast.get(id).children() {
+        visitor.visit_expr_mut(ast, child);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::scanner::Scanner;
+
+    fn parse(src: &str) -> Ast {
+        let tokens = Scanner::new(src.to_string())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        Parser::new(tokens).parse().unwrap()
+    }
+
+    /// Collects the names of the variables read, relying on the default
+    /// walks to reach them.
+    struct Reads(Vec<String>);
+
+    impl Visitor for Reads {
+        fn visit_variable(&mut self, _ast: &Ast, _id: ExprId, node: &VariableNode) {
+            self.0.push(node.name().token_type.to_string());
+        }
+    }
+
+    #[test]
+    fn default_walks_reach_every_expression() {
+        let ast = parse(
+            "var a = b; { print c ? d : e; } f = g(h, \"${i}\");
+            fun j() { if (k) return l; else while (m) n; }",
+        );
+        let mut reads = Reads(Vec::new());
+        reads.visit_program(&ast);
+        assert_eq!(
+            reads.0,
+            vec!["b", "c", "d", "e", "g", "h", "i", "k", "l", "m", "n"]
+        );
+    }
+
+    /// Counts `print` statements and literals.
+    struct Count {
+        prints: usize,
+        literals: usize,
+    }
+
+    impl VisitorMut for Count {
+        fn visit_print_stmt_mut(&mut self, ast: &mut Ast, id: StmtId) {
+            self.prints += 1;
+            walk_stmt_children_mut(self, ast, id);
+        }
+
+        fn visit_literal_mut(&mut self, _ast: &mut Ast, _id: ExprId) {
+            self.literals += 1;
+        }
+    }
+
+    #[test]
+    fn statement_hooks_of_visitor_mut() {
+        let mut ast = parse("print 1; { print 2 + 3; var x = 4; }");
+        let mut count = Count {
+            prints: 0,
+            literals: 0,
+        };
+        count.visit_program_mut(&mut ast);
+        assert_eq!(count.prints, 2);
+        assert_eq!(count.literals, 4);
+    }
+}
@@ -0,0 +1,45 @@
+use std::process::{Command, Output, Stdio};
+
+fn rlox(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rlox"))
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn bad_command_lines_exit_with_64() {
+    let table: &[&[&str]] = &[
+        &["--frobnicate"],
+        &["-e"],
+        &["-e", "print 1;", "--stdin", "--width=5", "--check"],
+        &["check", "--emit=dot", "a.lox"],
+        &["repl", "-O"],
+        &["fmt"],
+    ];
+    for args in table {
+        let output = rlox(args);
+        assert_eq!(output.status.code(), Some(64), "{:?}", args);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("Usage: rlox"), "{:?}", args);
+    }
+}
+
+#[test]
+fn eval_runs_with_its_arguments() {
+    let output = rlox(&["-e", "print args();", "--", "first"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "[first]\n");
+}
+
+#[test]
+fn scripts_read_their_arguments() {
+    let path = std::env::temp_dir().join(format!("rlox-args-{}.lox", std::process::id()));
+    std::fs::write(&path, "print argc();\nprint arg(1);\n").unwrap();
+
+    let output = rlox(&[path.to_str().unwrap(), "first", "second"]);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "2\nsecond\n");
+}